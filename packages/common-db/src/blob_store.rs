@@ -0,0 +1,217 @@
+//! Armazenamento de blobs sensíveis (áudio, anexos) com criptografia de envelope
+//!
+//! Cada blob é cifrado com uma chave de dados própria, gerada na hora do `put`, que por sua
+//! vez é embrulhada sob a chave mestra - o mesmo padrão que os modelos já seguem nas colunas
+//! `*_key_ciphertext`/`*_key_nonce` (ex.: `EncryptedAnamnesis::audio_key_ciphertext`). Isso dá
+//! ao ecossistema da clínica um único lugar testável para guardar áudio/anexos, em vez de
+//! espalhar I/O de arquivo e manuseio de chaves pelas aplicações.
+
+use anyhow::{Context, Result};
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::crypto::{self, CryptoError, EncryptedData, EncryptionKey, Secret};
+
+/// Chave de dados de um blob, embrulhada sob a chave mestra - é isso que deve ser persistido
+/// junto ao registro dono do blob (ex. em `audio_key_ciphertext`/`audio_key_nonce`)
+#[derive(Debug, Clone)]
+pub struct WrappedDataKey {
+    /// Chave de dados criptografada sob a chave mestra
+    pub ciphertext: Vec<u8>,
+    /// Nonce usado para criptografar a chave de dados
+    pub nonce: Vec<u8>,
+}
+
+/// Resultado de um `put`: identificador/caminho onde o blob foi armazenado e sua chave de
+/// dados embrulhada sob a chave mestra
+#[derive(Debug, Clone)]
+pub struct StoredBlob {
+    /// Identificador opaco do blob dentro do `BlobStore` (ex. nome de arquivo, chave S3)
+    pub handle: String,
+    /// Chave de dados do blob, embrulhada sob a chave mestra
+    pub data_key: WrappedDataKey,
+}
+
+/// Armazenamento de blobs com criptografia de envelope transparente
+///
+/// Implementações devem gerar uma [`EncryptionKey`] nova por blob, cifrar o blob com ela e
+/// embrulhar essa chave de dados sob a chave mestra - nunca gravar plaintext em disco.
+pub trait BlobStore {
+    /// Cifra `data` com uma chave de dados nova e persiste o blob cifrado, retornando seu
+    /// identificador e a chave de dados embrulhada sob `master_key`
+    async fn put(&self, data: &[u8], master_key: &EncryptionKey) -> Result<StoredBlob>;
+
+    /// Desembrulha a chave de dados de `blob` sob `master_key` e descriptografa o blob
+    async fn get(&self, blob: &StoredBlob, master_key: &EncryptionKey) -> Result<Secret<Vec<u8>>>;
+
+    /// Remove o blob identificado por `handle` (não é erro se já não existir)
+    async fn delete(&self, handle: &str) -> Result<()>;
+
+    /// Verifica se existe um blob armazenado sob `handle`
+    async fn exists(&self, handle: &str) -> Result<bool>;
+}
+
+/// Monta o AAD que vincula o ciphertext do blob (e sua chave de dados embrulhada) ao handle
+///
+/// `pub(crate)` porque a rotação de chave de dados em [`crate::migrations`] precisa recompor
+/// este mesmo AAD para re-embrulhar `*_key_ciphertext`/`*_key_nonce` (ex.:
+/// `EncryptedAnamnesis::audio_key_ciphertext`) sob a nova chave mestra.
+pub(crate) fn blob_domain(handle: &str) -> Vec<u8> {
+    crypto::field_domain("blob", handle, "data")
+}
+
+/// Implementação de [`BlobStore`] que grava os blobs cifrados em arquivos locais
+///
+/// Cada arquivo contém o tamanho do nonce (4 bytes, little-endian), o nonce do AES-256-GCM e,
+/// em seguida, o ciphertext. Deixa espaço para uma
+/// implementação equivalente sobre um backend compatível com S3 no futuro.
+pub struct LocalBlobStore {
+    base_dir: std::path::PathBuf,
+}
+
+impl LocalBlobStore {
+    /// Cria um store que grava os blobs dentro de `base_dir`; o diretório é criado no `put`,
+    /// se preciso
+    pub fn new(base_dir: impl Into<std::path::PathBuf>) -> Self {
+        Self { base_dir: base_dir.into() }
+    }
+
+    fn path_for(&self, handle: &str) -> std::path::PathBuf {
+        self.base_dir.join(handle)
+    }
+}
+
+impl BlobStore for LocalBlobStore {
+    async fn put(&self, data: &[u8], master_key: &EncryptionKey) -> Result<StoredBlob> {
+        tokio::fs::create_dir_all(&self.base_dir)
+            .await
+            .context("Falha ao criar diretório de blobs")?;
+
+        let handle = Uuid::new_v4().to_string();
+        let aad = blob_domain(&handle);
+
+        // Cifra o conteúdo com uma chave de dados nova, descartável após este blob
+        let data_key = EncryptionKey::generate();
+        let encrypted = crypto::encrypt_with_aad(data, &data_key, &aad)?;
+
+        let mut file = tokio::fs::File::create(self.path_for(&handle))
+            .await
+            .context("Falha ao criar arquivo de blob")?;
+        file.write_all(&(encrypted.nonce.len() as u32).to_le_bytes())
+            .await
+            .context("Falha ao gravar blob")?;
+        file.write_all(&encrypted.nonce).await.context("Falha ao gravar blob")?;
+        file.write_all(&encrypted.ciphertext).await.context("Falha ao gravar blob")?;
+        file.flush().await.context("Falha ao gravar blob")?;
+
+        // Embrulha a chave de dados sob a chave mestra, com o mesmo AAD do blob
+        let wrapped_data_key = crypto::encrypt_with_aad(data_key.as_bytes(), master_key, &aad)?;
+
+        Ok(StoredBlob {
+            handle,
+            data_key: WrappedDataKey {
+                ciphertext: wrapped_data_key.ciphertext,
+                nonce: wrapped_data_key.nonce,
+            },
+        })
+    }
+
+    async fn get(&self, blob: &StoredBlob, master_key: &EncryptionKey) -> Result<Secret<Vec<u8>>> {
+        let aad = blob_domain(&blob.handle);
+
+        let wrapped_data_key = EncryptedData {
+            ciphertext: blob.data_key.ciphertext.clone(),
+            nonce: blob.data_key.nonce.clone(),
+        };
+        let data_key_bytes = crypto::decrypt_with_aad(&wrapped_data_key, master_key, &aad)?;
+        let data_key = EncryptionKey::from_bytes(data_key_bytes.expose())?;
+
+        let raw = tokio::fs::read(self.path_for(&blob.handle))
+            .await
+            .context("Falha ao ler blob")?;
+
+        if raw.len() < 4 {
+            return Err(CryptoError::InvalidData("Arquivo de blob truncado".to_string()).into());
+        }
+        let nonce_len = u32::from_le_bytes(raw[0..4].try_into().unwrap()) as usize;
+        if raw.len() < 4 + nonce_len {
+            return Err(CryptoError::InvalidData("Arquivo de blob truncado".to_string()).into());
+        }
+        let encrypted = EncryptedData {
+            nonce: raw[4..4 + nonce_len].to_vec(),
+            ciphertext: raw[4 + nonce_len..].to_vec(),
+        };
+
+        crypto::decrypt_with_aad(&encrypted, &data_key, &aad)
+    }
+
+    async fn delete(&self, handle: &str) -> Result<()> {
+        match tokio::fs::remove_file(self.path_for(handle)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e).context("Falha ao remover blob"),
+        }
+    }
+
+    async fn exists(&self, handle: &str) -> Result<bool> {
+        Ok(tokio::fs::try_exists(self.path_for(handle))
+            .await
+            .context("Falha ao verificar existência do blob")?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_put_get_roundtrip() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let store = LocalBlobStore::new(temp_dir.path());
+        let master_key = EncryptionKey::generate();
+
+        let data = b"audio transcrito da consulta";
+        let stored = store.put(data, &master_key).await?;
+
+        assert!(store.exists(&stored.handle).await?);
+
+        let fetched = store.get(&stored, &master_key).await?;
+        assert_eq!(fetched.expose().as_slice(), data);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_fails_with_wrong_master_key() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let store = LocalBlobStore::new(temp_dir.path());
+        let master_key = EncryptionKey::generate();
+        let other_key = EncryptionKey::generate();
+
+        let stored = store.put(b"dados sensiveis", &master_key).await?;
+
+        let result = store.get(&stored, &other_key).await;
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_removes_blob() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let store = LocalBlobStore::new(temp_dir.path());
+        let master_key = EncryptionKey::generate();
+
+        let stored = store.put(b"dados", &master_key).await?;
+        assert!(store.exists(&stored.handle).await?);
+
+        store.delete(&stored.handle).await?;
+        assert!(!store.exists(&stored.handle).await?);
+
+        // Remover de novo não deve ser erro
+        store.delete(&stored.handle).await?;
+
+        Ok(())
+    }
+}
@@ -3,15 +3,16 @@
 //! Este módulo implementa as primitivas de criptografia para proteger
 //! dados sensíveis no banco de dados e em arquivos.
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use aes_gcm::{
-    aead::{Aead, AeadCore, KeyInit, OsRng},
+    aead::{self, Aead, AeadCore, KeyInit, OsRng},
     Aes256Gcm, Key, Nonce,
 };
+use argon2::{Algorithm, Argon2, Params, Version};
 use chacha20poly1305::{ChaCha20Poly1305, Key as ChaChaKey};
 use rand::{RngCore, rngs::OsRng as RandOsRng};
 use thiserror::Error;
-use zeroize::Zeroize;
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
 /// Erros específicos para operações de criptografia
 #[derive(Error, Debug)]
@@ -32,9 +33,65 @@ pub enum CryptoError {
     MasterKeyNotFound,
 }
 
+/// Wrapper para dados sensíveis (senhas, plaintext descriptografado) que devem ser
+/// zerados da memória assim que saem de escopo.
+///
+/// O conteúdo só é acessível via [`Secret::expose`], de forma explícita; `Debug`/`Display`
+/// nunca imprimem o valor real, para dificultar que ele vaze em logs por acidente.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct Secret<T: Zeroize>(T);
+
+impl<T: Zeroize> Secret<T> {
+    /// Encapsula um valor sensível
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// Expõe o conteúdo, de forma explícita - o chamador opta por manipular o valor real
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Zeroize> std::fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[REDACTED]")
+    }
+}
+
+impl<T: Zeroize> std::fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[REDACTED]")
+    }
+}
+
+/// Senha em texto puro - mantida viva apenas o tempo necessário e zerada da memória ao
+/// sair de escopo, em vez de circular como `&str`/`String` comum
+pub type SafePassword = Secret<String>;
+
+impl Secret<String> {
+    /// Cria uma `SafePassword` a partir de uma string emprestada
+    pub fn from_str(value: &str) -> Self {
+        Self::new(value.to_string())
+    }
+}
+
 /// Tamanho do nonce em bytes para AES-GCM
 const AES_GCM_NONCE_SIZE: usize = 12;
 
+/// Tamanho do salt em bytes usado na derivação Argon2id da chave de embrulho
+const WRAP_SALT_SIZE: usize = 16;
+
+/// Versão atual do esquema de derivação de chave (permite evoluir os parâmetros no futuro)
+pub const KEY_DERIVATION_VERSION: i32 = 1;
+
+/// Custo de memória padrão do Argon2id (em KiB) para derivar a chave de embrulho
+pub const DEFAULT_M_COST: u32 = 19_456;
+/// Número de iterações padrão do Argon2id
+pub const DEFAULT_T_COST: u32 = 2;
+/// Grau de paralelismo padrão do Argon2id
+pub const DEFAULT_P_COST: u32 = 1;
+
 /// Chave AES-256 para criptografia (com zeroização automática)
 #[derive(Clone, Zeroize)]
 #[zeroize(drop)]
@@ -76,106 +133,269 @@ pub struct EncryptedData {
     pub nonce: Vec<u8>,
 }
 
-/// Criptografa dados usando AES-256-GCM
+/// Monta uma string de domínio canônica para ligar um campo criptografado ao registro e
+/// coluna a que pertence (ex.: `"anamnesis:<id>:data"`), usada como AAD.
+///
+/// Isso impede que um par ciphertext/nonce de um registro seja "transplantado" para outro
+/// registro (ou outra coluna do mesmo registro) e ainda assim seja aceito na descriptografia.
+pub fn field_domain(entity: &str, id: impl std::fmt::Display, field: &str) -> Vec<u8> {
+    format!("{entity}:{id}:{field}").into_bytes()
+}
+
+/// Criptografa dados usando AES-256-GCM, sem dados associados (AAD vazio)
+///
+/// Mantido como atalho de compatibilidade; prefira [`encrypt_with_aad`] para vincular o
+/// ciphertext ao registro e coluna a que pertence.
 pub fn encrypt(data: &[u8], key: &EncryptionKey) -> Result<EncryptedData> {
+    encrypt_with_aad(data, key, &[])
+}
+
+/// Descriptografa dados usando AES-256-GCM, sem dados associados (AAD vazio)
+///
+/// Mantido como atalho de compatibilidade; prefira [`decrypt_with_aad`] para exigir o mesmo
+/// AAD usado na criptografia.
+pub fn decrypt(encrypted: &EncryptedData, key: &EncryptionKey) -> Result<Secret<Vec<u8>>> {
+    decrypt_with_aad(encrypted, key, &[])
+}
+
+/// Criptografa dados usando AES-256-GCM, vinculando o ciphertext a `aad` (dados associados)
+///
+/// `aad` não é cifrado, mas participa da tag de autenticação: descriptografar com um `aad`
+/// diferente do usado aqui falha, mesmo com a chave correta.
+pub fn encrypt_with_aad(data: &[u8], key: &EncryptionKey, aad: &[u8]) -> Result<EncryptedData> {
     // Criar cipher AES-256-GCM
     let aes_key = Key::<Aes256Gcm>::from_slice(key.as_bytes());
     let cipher = Aes256Gcm::new(aes_key);
-    
+
     // Gerar nonce aleatório
     let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
-    
-    // Criptografar dados
-    let ciphertext = cipher.encrypt(&nonce, data)
+
+    // Criptografar dados com os dados associados vinculados
+    let ciphertext = cipher
+        .encrypt(&nonce, aead::Payload { msg: data, aad })
         .map_err(|e| CryptoError::EncryptionFailed(e.to_string()))?;
-    
+
     Ok(EncryptedData {
         ciphertext,
         nonce: nonce.to_vec(),
     })
 }
 
-/// Descriptografa dados usando AES-256-GCM
-pub fn decrypt(encrypted: &EncryptedData, key: &EncryptionKey) -> Result<Vec<u8>> {
+/// Descriptografa dados usando AES-256-GCM, exigindo o mesmo `aad` usado na criptografia
+///
+/// Retorna o plaintext envolto em [`Secret`] - o chamador precisa optar explicitamente por
+/// `expose()` para manipular os bytes reais, e eles são zerados da memória ao sair de escopo.
+/// Um `aad` divergente do usado em `encrypt_with_aad` falha com [`CryptoError::DecryptionFailed`].
+pub fn decrypt_with_aad(encrypted: &EncryptedData, key: &EncryptionKey, aad: &[u8]) -> Result<Secret<Vec<u8>>> {
     // Criar cipher AES-256-GCM
     let aes_key = Key::<Aes256Gcm>::from_slice(key.as_bytes());
     let cipher = Aes256Gcm::new(aes_key);
-    
+
     // Verificar nonce
     if encrypted.nonce.len() != AES_GCM_NONCE_SIZE {
         return Err(CryptoError::InvalidData(
-            format!("Nonce inválido: esperado {} bytes, recebido {}", 
+            format!("Nonce inválido: esperado {} bytes, recebido {}",
                     AES_GCM_NONCE_SIZE, encrypted.nonce.len())
         ).into());
     }
-    
+
     let nonce = Nonce::from_slice(&encrypted.nonce);
-    
-    // Descriptografar
-    let plaintext = cipher.decrypt(nonce, encrypted.ciphertext.as_ref())
+
+    // Descriptografar, validando que o aad bate com o usado na criptografia
+    let plaintext = cipher
+        .decrypt(nonce, aead::Payload { msg: encrypted.ciphertext.as_ref(), aad })
         .map_err(|e| CryptoError::DecryptionFailed(e.to_string()))?;
-    
-    Ok(plaintext)
+
+    Ok(Secret::new(plaintext))
+}
+
+/// Chave mestra ou de dados encapsulada (wrapped) sob uma senha, via Argon2id + ChaCha20-Poly1305
+///
+/// Guarda o salt e os parâmetros do Argon2id usados na derivação para que `unwrap_key`
+/// consiga reproduzir exatamente a mesma chave de embrulho a partir da senha.
+#[derive(Debug, Clone)]
+pub struct WrappedKey {
+    /// Chave criptografada
+    pub ciphertext: Vec<u8>,
+    /// Nonce usado na criptografia
+    pub nonce: Vec<u8>,
+    /// Salt usado na derivação Argon2id da chave de embrulho
+    pub salt: Vec<u8>,
+    /// Custo de memória do Argon2id (em KiB)
+    pub m_cost: u32,
+    /// Número de iterações do Argon2id
+    pub t_cost: u32,
+    /// Grau de paralelismo do Argon2id
+    pub p_cost: u32,
+    /// Versão do esquema de derivação de chave usado
+    pub key_version: i32,
 }
 
-/// Criptografa uma chave usando ChaCha20-Poly1305 com chave derivada de senha
-pub fn wrap_key(key: &EncryptionKey, password: &str) -> Result<EncryptedData> {
-    // Na implementação completa, derivaríamos a chave de embrulho usando Argon2id
-    // Por hora, usamos um hash simples para demonstração
+/// Deriva a chave de embrulho a partir da senha usando Argon2id com os parâmetros informados
+fn derive_wrapping_key(
+    password: &SafePassword,
+    salt: &[u8],
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+) -> Result<[u8; 32]> {
+    let params = Params::new(m_cost, t_cost, p_cost, Some(32))
+        .map_err(|e| CryptoError::InvalidConfiguration(e.to_string()))?;
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+
     let mut wrapping_key = [0u8; 32];
-    
-    // Simples derivação para demonstração - NÃO USAR EM PRODUÇÃO
-    // Na versão completa, usar Argon2id com salt e params adequados
-    for (i, byte) in password.as_bytes().iter().enumerate() {
-        wrapping_key[i % 32] ^= byte;
-    }
-    
-    // Criar cipher ChaCha20-Poly1305
-    let chacha_key = ChaChaKey::from_slice(&wrapping_key);
+    argon2
+        .hash_password_into(password.expose().as_bytes(), salt, &mut wrapping_key)
+        .map_err(|e| CryptoError::InvalidConfiguration(e.to_string()))?;
+
+    Ok(wrapping_key)
+}
+
+/// Criptografa `data` com ChaCha20-Poly1305 sob uma chave de embrulho já derivada
+fn chacha_encrypt(wrapping_key: &[u8; 32], data: &[u8]) -> Result<EncryptedData> {
+    let chacha_key = ChaChaKey::from_slice(wrapping_key);
     let cipher = ChaCha20Poly1305::new(chacha_key);
-    
-    // Gerar nonce aleatório
+
     let mut nonce = [0u8; 12];
     RandOsRng.fill_bytes(&mut nonce);
-    
-    // Criptografar a chave
-    let ciphertext = cipher.encrypt(nonce.as_ref().into(), key.as_bytes())
+
+    let ciphertext = cipher.encrypt(nonce.as_ref().into(), data)
         .map_err(|e| CryptoError::EncryptionFailed(e.to_string()))?;
-    
+
     Ok(EncryptedData {
         ciphertext,
         nonce: nonce.to_vec(),
     })
 }
 
-/// Descriptografa uma chave usando ChaCha20-Poly1305 com chave derivada de senha
-pub fn unwrap_key(encrypted: &EncryptedData, password: &str) -> Result<EncryptionKey> {
-    // Mesma derivação simplificada da função wrap_key
-    let mut wrapping_key = [0u8; 32];
-    
-    for (i, byte) in password.as_bytes().iter().enumerate() {
-        wrapping_key[i % 32] ^= byte;
-    }
-    
-    // Criar cipher ChaCha20-Poly1305
-    let chacha_key = ChaChaKey::from_slice(&wrapping_key);
+/// Descriptografa com ChaCha20-Poly1305 sob uma chave de embrulho já derivada
+fn chacha_decrypt(wrapping_key: &[u8; 32], encrypted: &EncryptedData) -> Result<Vec<u8>> {
+    let chacha_key = ChaChaKey::from_slice(wrapping_key);
     let cipher = ChaCha20Poly1305::new(chacha_key);
-    
-    // Verificar nonce
+
     if encrypted.nonce.len() != 12 {
         return Err(CryptoError::InvalidData(
             format!("Nonce inválido: esperado 12 bytes, recebido {}", encrypted.nonce.len())
         ).into());
     }
-    
-    // Descriptografar
-    let plaintext = cipher.decrypt(encrypted.nonce.as_slice().into(), encrypted.ciphertext.as_ref())
-        .map_err(|e| CryptoError::DecryptionFailed(e.to_string()))?;
-    
+
+    cipher.decrypt(encrypted.nonce.as_slice().into(), encrypted.ciphertext.as_ref())
+        .map_err(|e| CryptoError::DecryptionFailed(e.to_string()).into())
+}
+
+/// Criptografa uma chave usando ChaCha20-Poly1305, com chave de embrulho derivada da senha
+/// via Argon2id (parâmetros padrão, salt aleatório de 16 bytes)
+pub fn wrap_key(key: &EncryptionKey, password: &SafePassword) -> Result<WrappedKey> {
+    wrap_key_with_params(key, password, DEFAULT_M_COST, DEFAULT_T_COST, DEFAULT_P_COST)
+}
+
+/// Mesma coisa que `wrap_key`, mas permite ajustar os parâmetros de custo do Argon2id
+pub fn wrap_key_with_params(
+    key: &EncryptionKey,
+    password: &SafePassword,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+) -> Result<WrappedKey> {
+    let mut salt = [0u8; WRAP_SALT_SIZE];
+    RandOsRng.fill_bytes(&mut salt);
+
+    let wrapping_key = derive_wrapping_key(password, &salt, m_cost, t_cost, p_cost)?;
+    let encrypted = chacha_encrypt(&wrapping_key, key.as_bytes())?;
+
+    Ok(WrappedKey {
+        ciphertext: encrypted.ciphertext,
+        nonce: encrypted.nonce,
+        salt: salt.to_vec(),
+        m_cost,
+        t_cost,
+        p_cost,
+        key_version: KEY_DERIVATION_VERSION,
+    })
+}
+
+/// Descriptografa uma chave usando ChaCha20-Poly1305, reproduzindo a chave de embrulho
+/// a partir da senha com o salt e os parâmetros Argon2id armazenados em `wrapped`
+pub fn unwrap_key(wrapped: &WrappedKey, password: &SafePassword) -> Result<EncryptionKey> {
+    if wrapped.key_version != KEY_DERIVATION_VERSION {
+        return Err(CryptoError::InvalidConfiguration(
+            format!("Versão de derivação de chave não suportada: {}", wrapped.key_version)
+        ).into());
+    }
+
+    let wrapping_key = derive_wrapping_key(
+        password,
+        &wrapped.salt,
+        wrapped.m_cost,
+        wrapped.t_cost,
+        wrapped.p_cost,
+    )?;
+
+    // Descriptografar - com salt/params errados ou senha errada, a tag AEAD não bate
+    let encrypted = EncryptedData {
+        ciphertext: wrapped.ciphertext.clone(),
+        nonce: wrapped.nonce.clone(),
+    };
+    let plaintext = chacha_decrypt(&wrapping_key, &encrypted)?;
+
     // Converter em EncryptionKey
     EncryptionKey::from_bytes(&plaintext)
 }
 
+/// Criptografa um valor sentinela fixo com a mesma chave de embrulho usada para embrulhar
+/// uma chave mestra (`wrapped`), permitindo depois validar a senha sem desembrulhar a chave
+/// real - basta tentar abrir o sentinela e checar se a tag AEAD bate.
+pub fn encrypt_sentinel(sentinel: &[u8], password: &SafePassword, wrapped: &WrappedKey) -> Result<EncryptedData> {
+    let wrapping_key = derive_wrapping_key(
+        password,
+        &wrapped.salt,
+        wrapped.m_cost,
+        wrapped.t_cost,
+        wrapped.p_cost,
+    )?;
+    chacha_encrypt(&wrapping_key, sentinel)
+}
+
+/// Verifica se `password` é a senha correta para `wrapped`, tentando abrir `encrypted`
+/// (o blob de verificação produzido por [`encrypt_sentinel`]) e comparando com `sentinel`.
+/// Qualquer falha de AEAD (senha errada) resulta em `Ok(false)`, nunca em erro.
+pub fn verify_sentinel(
+    encrypted: &EncryptedData,
+    sentinel: &[u8],
+    password: &SafePassword,
+    wrapped: &WrappedKey,
+) -> Result<bool> {
+    let wrapping_key = derive_wrapping_key(
+        password,
+        &wrapped.salt,
+        wrapped.m_cost,
+        wrapped.t_cost,
+        wrapped.p_cost,
+    )?;
+
+    match chacha_decrypt(&wrapping_key, encrypted) {
+        Ok(plaintext) => Ok(plaintext == sentinel),
+        Err(_) => Ok(false),
+    }
+}
+
+/// Modelos que materializam seus campos sensíveis como ciphertext/nonce e só trafegam
+/// plaintext através de uma visão descriptografada carregada internamente.
+///
+/// O struct persistido (linha do banco) deve ficar sempre em sua forma criptografada;
+/// implementar este trait é o único caminho previsto para ida e volta a plaintext, seguindo
+/// o padrão usado em wallets de forçar a criptografia antes de qualquer persistência.
+pub trait Encryptable {
+    /// Criptografa os campos sensíveis pendentes, usando `key` e o domínio AAD de cada campo
+    fn encrypt_fields(&mut self, key: &EncryptionKey) -> Result<()>;
+
+    /// Descriptografa os campos sensíveis para a visão interna, usando `key`
+    fn decrypt_fields(&mut self, key: &EncryptionKey) -> Result<()>;
+
+    /// Monta o AAD canônico de um campo deste modelo (ver [`field_domain`])
+    fn domain(&self, field_name: &str) -> Vec<u8>;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -196,59 +416,87 @@ mod tests {
         
         // Descriptografar
         let decrypted = decrypt(&encrypted, &key)?;
-        
+
         // Verificar se recuperamos os dados originais
-        assert_eq!(&decrypted, data);
-        
+        assert_eq!(decrypted.expose().as_slice(), data);
+
         Ok(())
     }
-    
+
     #[test]
     fn test_key_wrapping() -> Result<()> {
         // Gerar chave
         let original_key = EncryptionKey::generate();
-        
+
         // Senha para proteger a chave
-        let password = "senha-forte-do-admin";
-        
+        let password = SafePassword::from_str("senha-forte-do-admin");
+
         // Encapsular a chave
-        let wrapped = wrap_key(&original_key, password)?;
-        
+        let wrapped = wrap_key(&original_key, &password)?;
+
         // Desencapsular a chave
-        let unwrapped_key = unwrap_key(&wrapped, password)?;
-        
+        let unwrapped_key = unwrap_key(&wrapped, &password)?;
+
         // Verificar se a chave original foi recuperada
         assert_eq!(original_key.as_bytes(), unwrapped_key.as_bytes());
-        
+
         // Tentar com senha errada
-        let result = unwrap_key(&wrapped, "senha-errada");
+        let wrong_password = SafePassword::from_str("senha-errada");
+        let result = unwrap_key(&wrapped, &wrong_password);
         assert!(result.is_err());
-        
+
         Ok(())
     }
-    
+
+    #[test]
+    fn test_secret_debug_redacts_contents() {
+        let secret = SafePassword::from_str("senha-forte-do-admin");
+        assert_eq!(format!("{:?}", secret), "[REDACTED]");
+        assert_eq!(format!("{}", secret), "[REDACTED]");
+    }
+
     #[test]
     fn test_encryption_with_different_keys() -> Result<()> {
         let data = b"Dados de teste";
-        
+
         // Gerar duas chaves diferentes
         let key1 = EncryptionKey::generate();
         let key2 = EncryptionKey::generate();
-        
+
         // Verificar que as chaves são diferentes
         assert_ne!(key1.as_bytes(), key2.as_bytes());
-        
+
         // Criptografar com a primeira chave
         let encrypted = encrypt(data, &key1)?;
-        
+
         // Tentar descriptografar com a segunda chave (deve falhar)
         let result = decrypt(&encrypted, &key2);
         assert!(result.is_err());
-        
+
         // Descriptografar com a chave correta
         let decrypted = decrypt(&encrypted, &key1)?;
-        assert_eq!(&decrypted, data);
-        
+        assert_eq!(decrypted.expose().as_slice(), data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_aad_binds_ciphertext_to_its_record() -> Result<()> {
+        let data = b"Hipotese diagnostica confidencial";
+        let key = EncryptionKey::generate();
+
+        let aad = field_domain("anamnesis", "rec-1", "diagnosis");
+        let encrypted = encrypt_with_aad(data, &key, &aad)?;
+
+        // Mesmo aad: descriptografa normalmente
+        let decrypted = decrypt_with_aad(&encrypted, &key, &aad)?;
+        assert_eq!(decrypted.expose().as_slice(), data);
+
+        // Ciphertext "transplantado" para outro registro (aad diferente): deve falhar
+        let other_aad = field_domain("anamnesis", "rec-2", "diagnosis");
+        let result = decrypt_with_aad(&encrypted, &key, &other_aad);
+        assert!(result.is_err());
+
         Ok(())
     }
 }
\ No newline at end of file
@@ -9,10 +9,13 @@
 use anyhow::{Context, Result};
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
 use std::path::Path;
-use tracing::{error, info};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
 
+pub mod blob_store;
 pub mod crypto;
 pub mod error;
+pub mod master_key;
 pub mod migrations;
 pub mod models;
 
@@ -28,6 +31,10 @@ pub struct DbConfig {
     pub max_connections: u32,
     /// Nível de trace do SQL (0-3)
     pub trace_level: u8,
+    /// Número máximo de tentativas de conexão antes de desistir (inclui a primeira tentativa)
+    pub max_retries: u32,
+    /// Tempo máximo total, em segundos, gasto tentando conectar antes de desistir
+    pub max_elapsed_secs: u64,
 }
 
 impl Default for DbConfig {
@@ -37,6 +44,60 @@ impl Default for DbConfig {
             key_phrase: "".to_string(), // Vazio por segurança, deve ser definido explicitamente
             max_connections: 5,
             trace_level: 0,
+            max_retries: 5,
+            max_elapsed_secs: 30,
+        }
+    }
+}
+
+/// Classifica se um erro de conexão do SQLx é transitório (vale a pena tentar de novo) ou
+/// permanente (configuração/autenticação inválida, não adianta retentar)
+fn is_transient_connect_error(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Io(io_err) => matches!(
+            io_err.kind(),
+            std::io::ErrorKind::ConnectionRefused
+                | std::io::ErrorKind::ConnectionReset
+                | std::io::ErrorKind::ConnectionAborted
+        ),
+        sqlx::Error::PoolTimedOut => true,
+        _ => false,
+    }
+}
+
+/// Tenta conectar ao SQLite com backoff exponencial, respeitando `config.max_retries` e
+/// `config.max_elapsed_secs`. Erros de configuração/autenticação falham imediatamente.
+async fn connect_with_retry(
+    connection_options: SqliteConnectOptions,
+    config: &DbConfig,
+) -> Result<SqlitePool> {
+    let start = Instant::now();
+    let mut attempt: u32 = 0;
+    let mut delay = Duration::from_millis(100);
+
+    loop {
+        attempt += 1;
+
+        match SqlitePoolOptions::new()
+            .max_connections(config.max_connections)
+            .connect_with(connection_options.clone())
+            .await
+        {
+            Ok(pool) => return Ok(pool),
+            Err(e) if attempt < config.max_retries
+                && is_transient_connect_error(&e)
+                && start.elapsed().as_secs() < config.max_elapsed_secs =>
+            {
+                warn!(
+                    "Tentativa {} de conexão com o banco falhou ({}), tentando novamente em {:?}",
+                    attempt, e, delay
+                );
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(Duration::from_secs(10));
+            }
+            Err(e) => {
+                return Err(e).context("Falha ao conectar ao banco de dados SQLite");
+            }
         }
     }
 }
@@ -66,12 +127,8 @@ pub async fn init_db_pool(config: &DbConfig) -> Result<SqlitePool> {
         .foreign_keys(true)
         .pragma("synchronous", "NORMAL");
 
-    // Cria o pool de conexões
-    let pool = SqlitePoolOptions::new()
-        .max_connections(config.max_connections)
-        .connect_with(connection_options)
-        .await
-        .context("Falha ao conectar ao banco de dados SQLite")?;
+    // Cria o pool de conexões, retentando com backoff exponencial em falhas transitórias
+    let pool = connect_with_retry(connection_options, config).await?;
 
     // Aplica migrações automáticas
     migrations::run_migrations(&pool).await
@@ -97,6 +154,7 @@ mod tests {
             key_phrase: "test_password".to_string(),
             max_connections: 2,
             trace_level: 3,
+            ..Default::default()
         };
         
         // Inicializar banco
@@ -108,7 +166,25 @@ mod tests {
             .await?;
             
         assert_eq!(result.0, 1);
-        
+
         Ok(())
     }
+
+    #[test]
+    fn test_is_transient_connect_error() {
+        let pool_timeout = sqlx::Error::PoolTimedOut;
+        assert!(is_transient_connect_error(&pool_timeout));
+
+        let refused = sqlx::Error::Io(std::io::Error::new(
+            std::io::ErrorKind::ConnectionRefused,
+            "conexão recusada",
+        ));
+        assert!(is_transient_connect_error(&refused));
+
+        let permission_denied = sqlx::Error::Io(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "acesso negado",
+        ));
+        assert!(!is_transient_connect_error(&permission_denied));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,252 @@
+//! Verificação e rotação da chave mestra
+//!
+//! Este módulo gerencia o ciclo de vida das linhas da tabela `master_keys`: criar a primeira
+//! chave mestra, validar a senha do admin sem precisar desembrulhar a chave real, e rotacionar
+//! para uma nova senha/versão.
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use sqlx::SqlitePool;
+use tracing::info;
+
+use crate::crypto::{self, EncryptedData, EncryptionKey, SafePassword, WrappedKey};
+use crate::models::MasterKey;
+
+/// Valor fixo cifrado junto com cada chave mestra para permitir validar a senha do admin sem
+/// desembrulhar a chave real
+const VERIFICATION_SENTINEL: &[u8] = b"handwrite-ocr-preprocessor:master-key-verification:v1";
+
+/// Monta um `WrappedKey` a partir de uma linha de `master_keys`, para reuso pelas funções de
+/// embrulho/desembrulho do módulo `crypto`
+fn wrapped_key_from_row(row: &MasterKey) -> WrappedKey {
+    WrappedKey {
+        ciphertext: row.wrapped_key_ciphertext.clone(),
+        nonce: row.wrapped_key_nonce.clone(),
+        salt: row.wrapped_key_salt.clone(),
+        m_cost: row.wrapped_key_m_cost as u32,
+        t_cost: row.wrapped_key_t_cost as u32,
+        p_cost: row.wrapped_key_p_cost as u32,
+        // Versão do esquema de derivação, não a `key_version` da linha: a tabela não guarda
+        // essa versão e toda linha é embrulhada com o esquema atual
+        key_version: crypto::KEY_DERIVATION_VERSION,
+    }
+}
+
+/// Insere uma nova linha em `master_keys`, embrulhando `key` sob `password` na versão
+/// `key_version` informada, junto com seu blob de verificação
+async fn insert_master_key(
+    pool: &SqlitePool,
+    key: &EncryptionKey,
+    password: &SafePassword,
+    key_version: i32,
+    active: bool,
+) -> Result<MasterKey> {
+    let wrapped = crypto::wrap_key(key, password)?;
+    let verification = crypto::encrypt_sentinel(VERIFICATION_SENTINEL, password, &wrapped)?;
+
+    let row: MasterKey = sqlx::query_as(
+        r#"
+        INSERT INTO master_keys (
+            created_at, active, wrapped_key_ciphertext, wrapped_key_nonce, wrapped_key_tag,
+            key_version, wrapped_key_salt, wrapped_key_m_cost, wrapped_key_t_cost,
+            wrapped_key_p_cost, verification_ciphertext, verification_nonce
+        )
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING *
+        "#,
+    )
+    .bind(Utc::now())
+    .bind(active)
+    .bind(&wrapped.ciphertext)
+    .bind(&wrapped.nonce)
+    .bind(Vec::<u8>::new())
+    .bind(key_version)
+    .bind(&wrapped.salt)
+    .bind(wrapped.m_cost as i32)
+    .bind(wrapped.t_cost as i32)
+    .bind(wrapped.p_cost as i32)
+    .bind(&verification.ciphertext)
+    .bind(&verification.nonce)
+    .fetch_one(pool)
+    .await
+    .context("Falha ao inserir chave mestra")?;
+
+    Ok(row)
+}
+
+/// Gera e persiste a primeira chave mestra (versão 1, ativa), embrulhada sob `password`
+pub async fn create_master_key(pool: &SqlitePool, password: &SafePassword) -> Result<MasterKey> {
+    let key = EncryptionKey::generate();
+    insert_master_key(pool, &key, password, 1, true).await
+}
+
+/// Busca a linha ativa em `master_keys`
+pub async fn get_active_master_key(pool: &SqlitePool) -> Result<MasterKey> {
+    sqlx::query_as("SELECT * FROM master_keys WHERE active = 1 ORDER BY key_version DESC LIMIT 1")
+        .fetch_one(pool)
+        .await
+        .context("Falha ao buscar chave mestra ativa")
+}
+
+/// Lista todas as versões de chave mestra, da mais recente para a mais antiga
+pub async fn list_master_key_versions(pool: &SqlitePool) -> Result<Vec<MasterKey>> {
+    sqlx::query_as("SELECT * FROM master_keys ORDER BY key_version DESC")
+        .fetch_all(pool)
+        .await
+        .context("Falha ao listar versões de chave mestra")
+}
+
+/// Valida `password` contra a chave mestra ativa, sem nunca precisar desembrulhar a chave
+/// real: uma falha de AEAD ao abrir o sentinela é tratada como "senha errada".
+pub async fn verify_master_password(pool: &SqlitePool, password: &SafePassword) -> Result<bool> {
+    let active = get_active_master_key(pool).await?;
+    let wrapped = wrapped_key_from_row(&active);
+    let verification = EncryptedData {
+        ciphertext: active.verification_ciphertext.clone(),
+        nonce: active.verification_nonce.clone(),
+    };
+
+    crypto::verify_sentinel(&verification, VERIFICATION_SENTINEL, password, &wrapped)
+}
+
+/// Desembrulha e retorna a chave mestra ativa, caso `password` esteja correta
+pub async fn unlock_master_key(pool: &SqlitePool, password: &SafePassword) -> Result<EncryptionKey> {
+    let active = get_active_master_key(pool).await?;
+    let wrapped = wrapped_key_from_row(&active);
+    crypto::unwrap_key(&wrapped, password)
+}
+
+/// Rotaciona a chave mestra: gera uma nova chave, embrulha sob `new_password` em uma versão
+/// incrementada, marca a versão anterior como inativa e retorna a nova linha.
+///
+/// `old_password` é usado só para confirmar que quem está rotacionando conhece a senha atual
+/// (via `verify_master_password`); a nova chave mestra é independente da anterior.
+///
+/// Isso cobre a rotação da própria chave mestra. Registros com dados envelopados sob a chave
+/// mestra antiga (chaves por arquivo/campo) continuam cifrados sob ela até serem migrados com
+/// [`crate::migrations::rotate_master_key`]; a linha antiga permanece em `master_keys`, inativa.
+/// As tabelas de dados não registram sob qual versão de chave mestra cada linha está cifrada.
+pub async fn rotate_master_key(
+    pool: &SqlitePool,
+    old_password: &SafePassword,
+    new_password: &SafePassword,
+) -> Result<MasterKey> {
+    let current = get_active_master_key(pool).await?;
+
+    if !verify_master_password(pool, old_password).await? {
+        bail!("Senha atual incorreta, rotação de chave mestra abortada");
+    }
+
+    let new_key = EncryptionKey::generate();
+    let next_version = current.key_version + 1;
+
+    let mut tx = pool.begin().await.context("Falha ao iniciar transação de rotação")?;
+
+    sqlx::query("UPDATE master_keys SET active = 0 WHERE id = ?")
+        .bind(current.id)
+        .execute(&mut *tx)
+        .await
+        .context("Falha ao desativar chave mestra anterior")?;
+
+    let wrapped = crypto::wrap_key(&new_key, new_password)?;
+    let verification = crypto::encrypt_sentinel(VERIFICATION_SENTINEL, new_password, &wrapped)?;
+
+    let new_row: MasterKey = sqlx::query_as(
+        r#"
+        INSERT INTO master_keys (
+            created_at, active, wrapped_key_ciphertext, wrapped_key_nonce, wrapped_key_tag,
+            key_version, wrapped_key_salt, wrapped_key_m_cost, wrapped_key_t_cost,
+            wrapped_key_p_cost, verification_ciphertext, verification_nonce
+        )
+        VALUES (?, 1, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING *
+        "#,
+    )
+    .bind(Utc::now())
+    .bind(&wrapped.ciphertext)
+    .bind(&wrapped.nonce)
+    .bind(Vec::<u8>::new())
+    .bind(next_version)
+    .bind(&wrapped.salt)
+    .bind(wrapped.m_cost as i32)
+    .bind(wrapped.t_cost as i32)
+    .bind(wrapped.p_cost as i32)
+    .bind(&verification.ciphertext)
+    .bind(&verification.nonce)
+    .fetch_one(&mut *tx)
+    .await
+    .context("Falha ao inserir nova versão de chave mestra")?;
+
+    tx.commit().await.context("Falha ao confirmar rotação de chave mestra")?;
+
+    info!("Chave mestra rotacionada para a versão {}", next_version);
+    Ok(new_row)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqliteConnectOptions;
+    use tempfile::tempdir;
+
+    async fn test_pool() -> Result<SqlitePool> {
+        let temp_dir = tempdir()?;
+        let db_path = temp_dir.path().join("test_master_key.db");
+        let conn_options = SqliteConnectOptions::new()
+            .filename(&db_path)
+            .create_if_missing(true);
+        let pool = SqlitePool::connect_with(conn_options).await?;
+        crate::migrations::run_migrations(&pool).await?;
+        Ok(pool)
+    }
+
+    #[tokio::test]
+    async fn test_create_and_verify_master_key() -> Result<()> {
+        let pool = test_pool().await?;
+        let password = SafePassword::from_str("senha-forte-do-admin");
+
+        create_master_key(&pool, &password).await?;
+
+        assert!(verify_master_password(&pool, &password).await?);
+
+        let wrong_password = SafePassword::from_str("senha-errada");
+        assert!(!verify_master_password(&pool, &wrong_password).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unlock_master_key_recovers_original_key() -> Result<()> {
+        let pool = test_pool().await?;
+        let password = SafePassword::from_str("senha-forte-do-admin");
+
+        let original_key = EncryptionKey::generate();
+        insert_master_key(&pool, &original_key, &password, 1, true).await?;
+        let unlocked = unlock_master_key(&pool, &password).await?;
+
+        assert_eq!(unlocked.as_bytes(), original_key.as_bytes());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rotate_master_key() -> Result<()> {
+        let pool = test_pool().await?;
+        let old_password = SafePassword::from_str("senha-antiga");
+        let new_password = SafePassword::from_str("senha-nova");
+
+        create_master_key(&pool, &old_password).await?;
+        let rotated = rotate_master_key(&pool, &old_password, &new_password).await?;
+
+        assert_eq!(rotated.key_version, 2);
+        assert!(rotated.active);
+
+        let versions = list_master_key_versions(&pool).await?;
+        assert_eq!(versions.len(), 2);
+        assert!(versions.iter().any(|v| v.key_version == 1 && !v.active));
+
+        assert!(verify_master_password(&pool, &new_password).await?);
+        assert!(!verify_master_password(&pool, &old_password).await?);
+
+        Ok(())
+    }
+}
@@ -1,277 +1,1263 @@
 //! Sistema de migrações para banco de dados
 //!
-//! Este módulo gerencia as migrações do banco de dados SQLite
+//! Este módulo gerencia as migrações do banco de dados SQLite. Os scripts em si moram em
+//! `migrations/` como pares de arquivo `NNN_nome.up.sql`/`NNN_nome.down.sql`, embutidos no
+//! binário em tempo de compilação - sem dependência de filesystem em runtime, mas com arquivos
+//! de verdade para revisar, diffar e lintar.
 
 use anyhow::{Context, Result};
-use sqlx::{migrate::MigrateDatabase, sqlite::SqlitePoolOptions, Sqlite, SqlitePool};
-use tracing::{error, info};
-
-/// Lista de migrações SQL a serem aplicadas
-const MIGRATIONS: &[&str] = &[
-    // 001_initial_schema.sql
-    r#"
-    -- Tabela de pacientes com dados criptografados
-    CREATE TABLE IF NOT EXISTS patients (
-        id TEXT PRIMARY KEY NOT NULL,
-        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
-        updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
-        name_ciphertext BLOB NOT NULL,
-        name_nonce BLOB NOT NULL,
-        phone_ciphertext BLOB,
-        phone_nonce BLOB,
-        email_ciphertext BLOB,
-        email_nonce BLOB,
-        consent_version TEXT,
-        consent_timestamp TIMESTAMP,
-        consent_scope TEXT, -- JSON com escopo de consentimento
-        consent_signature BLOB,
-        access_token_hash TEXT
-    );
-    
-    -- Tabela de agendamentos
-    CREATE TABLE IF NOT EXISTS appointments (
-        id TEXT PRIMARY KEY NOT NULL,
-        patient_id TEXT NOT NULL,
-        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
-        scheduled_at TIMESTAMP NOT NULL,
-        duration_minutes INTEGER NOT NULL DEFAULT 30,
-        status TEXT NOT NULL CHECK (status IN ('scheduled', 'confirmed', 'in_progress', 'completed', 'canceled', 'no_show')),
-        type TEXT NOT NULL,
-        notes_ciphertext BLOB,
-        notes_nonce BLOB,
-        source TEXT NOT NULL,
-        anamnesis_id TEXT,
-        reminder_sent BOOLEAN NOT NULL DEFAULT 0,
-        follow_up_sent BOOLEAN NOT NULL DEFAULT 0,
-        FOREIGN KEY (patient_id) REFERENCES patients (id) ON DELETE CASCADE
-    );
-    
-    -- Tabela de anamneses
-    CREATE TABLE IF NOT EXISTS anamneses (
-        id TEXT PRIMARY KEY NOT NULL,
-        appointment_id TEXT NOT NULL,
-        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
-        updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
-        data_ciphertext BLOB NOT NULL,
-        data_nonce BLOB NOT NULL,
-        audio_path TEXT,
-        audio_key_ciphertext BLOB,
-        audio_key_nonce BLOB,
-        transcription_complete BOOLEAN NOT NULL DEFAULT 0,
-        diagnosis_ciphertext BLOB,
-        diagnosis_nonce BLOB,
-        FOREIGN KEY (appointment_id) REFERENCES appointments (id) ON DELETE CASCADE
-    );
-    
-    -- Tabela de finanças
-    CREATE TABLE IF NOT EXISTS finances (
-        id TEXT PRIMARY KEY NOT NULL,
-        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
-        type TEXT NOT NULL CHECK (type IN ('income', 'expense')),
-        category TEXT NOT NULL,
-        amount DECIMAL(10, 2) NOT NULL,
-        date DATE NOT NULL,
-        description_ciphertext BLOB,
-        description_nonce BLOB,
-        appointment_id TEXT,
-        receipt_path TEXT,
-        receipt_key_ciphertext BLOB,
-        receipt_key_nonce BLOB,
-        FOREIGN KEY (appointment_id) REFERENCES appointments (id) ON DELETE SET NULL
-    );
-    
-    -- Tabela de métricas de redes sociais
-    CREATE TABLE IF NOT EXISTS social_metrics (
-        id TEXT PRIMARY KEY NOT NULL,
-        date DATE NOT NULL,
-        platform TEXT NOT NULL,
-        followers INTEGER NOT NULL DEFAULT 0,
-        engagement INTEGER NOT NULL DEFAULT 0,
-        reach INTEGER NOT NULL DEFAULT 0,
-        impressions INTEGER NOT NULL DEFAULT 0,
-        clicks INTEGER NOT NULL DEFAULT 0,
-        source TEXT NOT NULL
-    );
-    
-    -- Tabela de chaves mestras
-    CREATE TABLE IF NOT EXISTS master_keys (
-        id INTEGER PRIMARY KEY AUTOINCREMENT,
-        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
-        active BOOLEAN NOT NULL DEFAULT 0,
-        wrapped_key_ciphertext BLOB NOT NULL,
-        wrapped_key_nonce BLOB NOT NULL,
-        wrapped_key_tag BLOB NOT NULL,
-        key_version INTEGER NOT NULL
-    );
-    
-    -- Índices para otimização
-    CREATE INDEX IF NOT EXISTS idx_appointments_patient_id ON appointments (patient_id);
-    CREATE INDEX IF NOT EXISTS idx_appointments_scheduled_at ON appointments (scheduled_at);
-    CREATE INDEX IF NOT EXISTS idx_appointments_status ON appointments (status);
-    CREATE INDEX IF NOT EXISTS idx_anamneses_appointment_id ON anamneses (appointment_id);
-    CREATE INDEX IF NOT EXISTS idx_finances_date ON finances (date);
-    CREATE INDEX IF NOT EXISTS idx_finances_type ON finances (type);
-    CREATE INDEX IF NOT EXISTS idx_social_metrics_date ON social_metrics (date);
-    CREATE INDEX IF NOT EXISTS idx_social_metrics_platform ON social_metrics (platform);
-    "#,
-    
-    // 002_bridge_tables.sql
-    r#"
-    -- Tabela de integrações entre sistemas
-    CREATE TABLE IF NOT EXISTS system_integrations (
-        id TEXT PRIMARY KEY NOT NULL,
-        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
-        updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
-        source_system TEXT NOT NULL,
-        target_system TEXT NOT NULL,
-        integration_type TEXT NOT NULL,
-        config_json TEXT NOT NULL,
-        enabled BOOLEAN NOT NULL DEFAULT 1
-    );
-    
-    -- Tabela de mapeamento entre IDs de sistemas
-    CREATE TABLE IF NOT EXISTS entity_mappings (
-        id TEXT PRIMARY KEY NOT NULL,
-        source_system TEXT NOT NULL,
-        source_entity_type TEXT NOT NULL,
-        source_entity_id TEXT NOT NULL,
-        target_system TEXT NOT NULL,
-        target_entity_type TEXT NOT NULL,
-        target_entity_id TEXT NOT NULL,
-        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
-        UNIQUE(source_system, source_entity_type, source_entity_id, target_system, target_entity_type)
-    );
-    
-    -- Tabela de eventos de sincronização
-    CREATE TABLE IF NOT EXISTS sync_events (
-        id TEXT PRIMARY KEY NOT NULL,
-        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
-        source_system TEXT NOT NULL,
-        target_system TEXT NOT NULL,
-        entity_type TEXT NOT NULL,
-        entity_id TEXT NOT NULL,
-        event_type TEXT NOT NULL,
-        status TEXT NOT NULL CHECK (status IN ('pending', 'processing', 'completed', 'failed')),
-        error_message TEXT,
-        retry_count INTEGER NOT NULL DEFAULT 0,
-        last_attempt_at TIMESTAMP
-    );
-    
-    -- Índices para otimização
-    CREATE INDEX IF NOT EXISTS idx_entity_mappings_source ON entity_mappings (source_system, source_entity_type, source_entity_id);
-    CREATE INDEX IF NOT EXISTS idx_entity_mappings_target ON entity_mappings (target_system, target_entity_type, target_entity_id);
-    CREATE INDEX IF NOT EXISTS idx_sync_events_status ON sync_events (status);
-    CREATE INDEX IF NOT EXISTS idx_sync_events_entity ON sync_events (entity_type, entity_id);
-    "#,
-];
+use chrono::{DateTime, Utc};
+use include_dir::{include_dir, Dir};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{migrate::MigrateDatabase, sqlite::SqlitePoolOptions, Row, Sqlite, SqlitePool, Transaction};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+use std::time::Instant;
+use tracing::info;
 
-/// Executa todas as migrações pendentes no banco de dados
-pub async fn run_migrations(pool: &SqlitePool) -> Result<()> {
-    info!("Aplicando migrações de banco de dados...");
-    
-    // Obter a versão atual do banco de dados
-    let mut version: i64 = 0;
-    match sqlx::query_scalar("PRAGMA user_version")
+use crate::crypto::{self, EncryptedData, EncryptionKey};
+
+/// Diretório com os scripts de migração, embutido no binário em tempo de compilação
+static MIGRATIONS_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/migrations");
+
+/// Uma migração de schema, com o par de scripts de subida (`up`) e descida (`down`)
+struct Migration {
+    /// Versão da migração (prefixo numérico do nome do arquivo)
+    version: i64,
+    /// Nome descritivo da migração (ex. `initial_schema`)
+    name: String,
+    /// SQL aplicado ao subir para esta versão
+    up: &'static str,
+    /// SQL aplicado ao descer desta versão para a anterior
+    down: &'static str,
+}
+
+/// Direção de um arquivo de migração, conforme seu sufixo de nome
+enum Direction {
+    Up,
+    Down,
+}
+
+/// Extrai `(versão, nome, direção)` do nome de um arquivo de migração (`NNN_nome.up.sql` ou
+/// `NNN_nome.down.sql`); arquivos que não seguem esse padrão são ignorados
+fn parse_migration_filename(filename: &str) -> Option<(i64, String, Direction)> {
+    let (stem, direction) = if let Some(stem) = filename.strip_suffix(".up.sql") {
+        (stem, Direction::Up)
+    } else if let Some(stem) = filename.strip_suffix(".down.sql") {
+        (stem, Direction::Down)
+    } else {
+        return None;
+    };
+
+    let (version_str, name) = stem.split_once('_')?;
+    let version: i64 = version_str.parse().ok()?;
+
+    Some((version, name.to_string(), direction))
+}
+
+/// Carrega os scripts embutidos em `migrations/` e monta a lista de migrações, ordenada pela
+/// versão numérica extraída do nome de cada arquivo
+fn load_migrations() -> Vec<Migration> {
+    let mut by_version: BTreeMap<i64, (Option<String>, Option<&'static str>, Option<&'static str>)> =
+        BTreeMap::new();
+
+    for file in MIGRATIONS_DIR.files() {
+        let Some(filename) = file.path().file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let Some((version, name, direction)) = parse_migration_filename(filename) else {
+            continue;
+        };
+        let contents = std::str::from_utf8(file.contents())
+            .unwrap_or_else(|e| panic!("migração {} não é UTF-8 válido: {}", filename, e));
+
+        let entry = by_version.entry(version).or_insert((None, None, None));
+        entry.0.get_or_insert(name);
+        match direction {
+            Direction::Up => entry.1 = Some(contents),
+            Direction::Down => entry.2 = Some(contents),
+        }
+    }
+
+    by_version
+        .into_iter()
+        .map(|(version, (name, up, down))| Migration {
+            version,
+            name: name.unwrap_or_else(|| panic!("migração {} sem nome", version)),
+            up: up.unwrap_or_else(|| panic!("migração {} sem script .up.sql", version)),
+            down: down.unwrap_or_else(|| panic!("migração {} sem script .down.sql", version)),
+        })
+        .collect()
+}
+
+/// Migrações conhecidas, carregadas e ordenadas uma única vez
+fn migrations() -> &'static [Migration] {
+    static MIGRATIONS: OnceLock<Vec<Migration>> = OnceLock::new();
+    MIGRATIONS.get_or_init(load_migrations)
+}
+
+/// Busca uma migração conhecida por versão
+fn migration_by_version(version: i64) -> Option<&'static Migration> {
+    migrations().iter().find(|m| m.version == version)
+}
+
+/// Calcula o checksum (SHA-256) do script `up` de uma migração, usado para detectar se o
+/// histórico de uma migração já aplicada foi alterado depois do fato
+fn checksum(sql: &str) -> Vec<u8> {
+    Sha256::digest(sql.as_bytes()).to_vec()
+}
+
+/// Cria a tabela de controle `_migrations`, caso ainda não exista
+async fn ensure_migrations_table(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS _migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            checksum BLOB NOT NULL,
+            applied_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            execution_ms INTEGER NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("Falha ao criar tabela de controle de migrações")?;
+
+    Ok(())
+}
+
+/// Lê a versão atual do banco (maior `version` registrada em `_migrations`), 0 se nenhuma
+/// migração foi aplicada ainda
+async fn current_version(pool: &SqlitePool) -> Result<i64> {
+    let version: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM _migrations")
         .fetch_one(pool)
         .await
-    {
-        Ok(v) => version = v,
-        Err(e) => {
-            error!("Erro ao obter versão do banco: {}", e);
-            // Continuar mesmo assim, pois pode ser a primeira execução
+        .context("Falha ao obter versão do banco")?;
+
+    Ok(version.unwrap_or(0))
+}
+
+/// Confere o checksum de cada migração já aplicada (versão <= `up_to_version`) contra o
+/// checksum recalculado do script `up` correspondente, para flagrar edições no histórico de
+/// migrações em vez de deixá-las passar batido. Versões aplicadas mas desconhecidas deste
+/// binário não têm como ser conferidas aqui - `migration_status` é quem reporta essas.
+async fn verify_applied_checksums(pool: &SqlitePool, up_to_version: i64) -> Result<()> {
+    let applied: Vec<(i64, Vec<u8>)> =
+        sqlx::query_as("SELECT version, checksum FROM _migrations WHERE version <= ?")
+            .bind(up_to_version)
+            .fetch_all(pool)
+            .await
+            .context("Falha ao ler checksums das migrações aplicadas")?;
+
+    for (version, stored_checksum) in applied {
+        let Some(migration) = migration_by_version(version) else {
+            continue;
+        };
+        let expected_checksum = checksum(migration.up);
+
+        if stored_checksum != expected_checksum {
+            anyhow::bail!(
+                "Checksum da migração {} ({}) não confere com o script atual - o histórico de migrações foi alterado",
+                version,
+                migration.name
+            );
         }
     }
-    
+
+    Ok(())
+}
+
+/// Estado de uma migração em relação ao banco e ao binário atual
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationState {
+    /// Conhecida pelo binário, ainda não aplicada ao banco
+    Pending,
+    /// Conhecida pelo binário e aplicada ao banco
+    Applied,
+    /// Aplicada ao banco, mas ausente das migrações embutidas neste binário - o banco está à
+    /// frente do binário (ex. um rollback de deploy depois de uma migração já aplicada)
+    AppliedButUnknown,
+}
+
+/// Situação de uma migração, para exibição (o equivalente desta biblioteca a um `migrate list`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationStatus {
+    /// Versão da migração
+    pub version: i64,
+    /// Nome descritivo da migração
+    pub name: String,
+    /// Estado atual em relação ao banco conectado
+    pub state: MigrationState,
+    /// Data e hora em que foi aplicada, se já aplicada
+    pub applied_at: Option<DateTime<Utc>>,
+    /// Checksum registrado no momento em que foi aplicada, se já aplicada
+    pub checksum: Option<Vec<u8>>,
+}
+
+/// Relata, para cada migração conhecida e para qualquer migração aplicada ao banco mas
+/// desconhecida deste binário, seu estado atual - permite à aplicação avisar na inicialização
+/// se o banco está em dia, quais migrações estão pendentes, ou se o banco está à frente do
+/// binário.
+pub async fn migration_status(pool: &SqlitePool) -> Result<Vec<MigrationStatus>> {
+    ensure_migrations_table(pool).await?;
+
+    let applied: Vec<(i64, String, Vec<u8>, DateTime<Utc>)> = sqlx::query_as(
+        "SELECT version, name, checksum, applied_at FROM _migrations ORDER BY version",
+    )
+    .fetch_all(pool)
+    .await
+    .context("Falha ao ler migrações aplicadas")?;
+
+    let mut applied_by_version: HashMap<i64, (String, Vec<u8>, DateTime<Utc>)> = applied
+        .into_iter()
+        .map(|(version, name, checksum, applied_at)| (version, (name, checksum, applied_at)))
+        .collect();
+
+    let mut statuses = Vec::with_capacity(migrations().len());
+    for migration in migrations() {
+        let version = migration.version;
+
+        statuses.push(match applied_by_version.remove(&version) {
+            Some((name, checksum, applied_at)) => MigrationStatus {
+                version,
+                name,
+                state: MigrationState::Applied,
+                applied_at: Some(applied_at),
+                checksum: Some(checksum),
+            },
+            None => MigrationStatus {
+                version,
+                name: migration.name.clone(),
+                state: MigrationState::Pending,
+                applied_at: None,
+                checksum: None,
+            },
+        });
+    }
+
+    // O que sobrou em `applied_by_version` está no banco mas não nas migrações embutidas
+    // deste binário
+    let mut unknown: Vec<_> = applied_by_version.into_iter().collect();
+    unknown.sort_by_key(|(version, _)| *version);
+    for (version, (name, checksum, applied_at)) in unknown {
+        statuses.push(MigrationStatus {
+            version,
+            name,
+            state: MigrationState::AppliedButUnknown,
+            applied_at: Some(applied_at),
+            checksum: Some(checksum),
+        });
+    }
+
+    Ok(statuses)
+}
+
+/// Executa todas as migrações pendentes, até a última conhecida
+pub async fn run_migrations(pool: &SqlitePool) -> Result<()> {
+    let max_version = migrations().last().map(|m| m.version).unwrap_or(0);
+    run_migrations_to(pool, max_version).await
+}
+
+/// Executa as migrações pendentes até `target_version` (inclusive)
+///
+/// Rodar até a versão atual do banco é um no-op. Pedir uma versão menor que a atual (isso
+/// seria uma reversão, não uma subida - use [`revert_migrations`]) ou maior que a última
+/// migração conhecida é um erro, para que desvios de versão sejam visíveis em vez de
+/// silenciosamente ignorados.
+pub async fn run_migrations_to(pool: &SqlitePool, target_version: i64) -> Result<()> {
+    info!("Aplicando migrações de banco de dados...");
+
+    ensure_migrations_table(pool).await?;
+
+    let max_version = migrations().last().map(|m| m.version).unwrap_or(0);
+    if target_version > max_version {
+        anyhow::bail!(
+            "Versão alvo {} é maior que a última migração conhecida ({})",
+            target_version,
+            max_version
+        );
+    }
+
+    let version = current_version(pool).await?;
+    if target_version < version {
+        anyhow::bail!(
+            "Versão alvo {} é menor que a versão atual do banco ({}); use revert_migrations para reverter",
+            target_version,
+            version
+        );
+    }
+
+    verify_applied_checksums(pool, version).await?;
+
     info!("Versão atual do banco: {}", version);
-    
-    // Aplicar cada migração pendente sequencialmente
-    for (i, migration_sql) in MIGRATIONS.iter().enumerate() {
-        let migration_version = (i + 1) as i64;
-        
+
+    // Aplicar cada migração pendente sequencialmente, até a versão alvo
+    for migration in migrations() {
+        let migration_version = migration.version;
+
+        if migration_version > target_version {
+            break;
+        }
+
         // Pular migrações já aplicadas
         if migration_version <= version {
-            info!("Migração {} já aplicada", migration_version);
+            info!("Migração {} ({}) já aplicada", migration_version, migration.name);
             continue;
         }
-        
-        info!("Aplicando migração {}...", migration_version);
-        
+
+        info!("Aplicando migração {} ({})...", migration_version, migration.name);
+        let start = Instant::now();
+
         // Executar em uma transação para garantir atomicidade
         let mut transaction = pool.begin().await
             .context(format!("Falha ao iniciar transação para migração {}", migration_version))?;
-            
-        // Executar os comandos SQL
-        sqlx::query(migration_sql)
-            .execute(&mut transaction)
+
+        sqlx::query(migration.up)
+            .execute(&mut *transaction)
             .await
             .context(format!("Falha ao executar migração {}", migration_version))?;
-            
-        // Atualizar versão do banco
-        sqlx::query(&format!("PRAGMA user_version = {}", migration_version))
-            .execute(&mut transaction)
-            .await
-            .context(format!("Falha ao atualizar versão para {}", migration_version))?;
-            
-        // Commit da transação
+
+        let execution_ms = start.elapsed().as_millis() as i64;
+
+        sqlx::query(
+            "INSERT INTO _migrations (version, name, checksum, applied_at, execution_ms) VALUES (?, ?, ?, CURRENT_TIMESTAMP, ?)",
+        )
+        .bind(migration_version)
+        .bind(&migration.name)
+        .bind(checksum(migration.up))
+        .bind(execution_ms)
+        .execute(&mut *transaction)
+        .await
+        .context(format!("Falha ao registrar migração {}", migration_version))?;
+
         transaction.commit().await
             .context(format!("Falha ao confirmar transação para migração {}", migration_version))?;
-            
-        info!("Migração {} aplicada com sucesso", migration_version);
+
+        info!("Migração {} aplicada com sucesso em {}ms", migration_version, execution_ms);
     }
-    
-    info!("Migrações concluídas. Versão atual: {}", MIGRATIONS.len());
+
+    info!("Migrações concluídas. Versão atual: {}", target_version);
+    Ok(())
+}
+
+/// Reverte as migrações aplicadas até `target_version`, executando o script `down` de cada
+/// versão acima do alvo, da mais recente para a mais antiga.
+///
+/// Cada passo roda e é confirmado em sua própria transação, removendo o registro correspondente
+/// de `_migrations` - uma falha no meio do caminho deixa o banco em uma versão consistente (a
+/// última revertida com sucesso), não precisa recomeçar do zero.
+pub async fn revert_migrations(pool: &SqlitePool, target_version: i64) -> Result<()> {
+    ensure_migrations_table(pool).await?;
+
+    let max_version = migrations().last().map(|m| m.version).unwrap_or(0);
+    if target_version > max_version || target_version < 0 {
+        anyhow::bail!(
+            "Versão alvo {} fora do intervalo conhecido (0..={})",
+            target_version,
+            max_version
+        );
+    }
+
+    let mut version = current_version(pool).await?;
+    if target_version > version {
+        anyhow::bail!(
+            "Versão alvo {} é maior que a versão atual do banco ({}); use run_migrations para subir",
+            target_version,
+            version
+        );
+    }
+
+    info!("Revertendo migrações da versão {} até {}...", version, target_version);
+
+    while version > target_version {
+        let Some(migration) = migration_by_version(version) else {
+            anyhow::bail!(
+                "Migração {} está aplicada ao banco mas é desconhecida deste binário - não é possível reverter; use migration_status para inspecionar o banco",
+                version
+            );
+        };
+        info!("Revertendo migração {} ({})...", version, migration.name);
+
+        let mut transaction = pool.begin().await
+            .context(format!("Falha ao iniciar transação para reverter migração {}", version))?;
+
+        sqlx::query(migration.down)
+            .execute(&mut *transaction)
+            .await
+            .context(format!("Falha ao reverter migração {}", version))?;
+
+        sqlx::query("DELETE FROM _migrations WHERE version = ?")
+            .bind(version)
+            .execute(&mut *transaction)
+            .await
+            .context(format!("Falha ao remover registro da migração {}", version))?;
+
+        transaction.commit().await
+            .context(format!("Falha ao confirmar reversão da migração {}", version))?;
+
+        info!("Migração {} revertida com sucesso", version);
+        version -= 1;
+    }
+
+    Ok(())
+}
+
+/// Número de linhas processadas por lote/transação durante a rotação de chave de dados
+const ROTATION_BATCH_SIZE: i64 = 500;
+
+/// Um par `*_ciphertext`/`*_nonce` a ser re-embrulhado durante a rotação de chave
+struct EncryptedColumn {
+    /// Coluna com o ciphertext
+    ciphertext_column: &'static str,
+    /// Coluna com o nonce
+    nonce_column: &'static str,
+    /// Se verdadeiro, a coluna pode estar NULL (campo opcional, nada a rotacionar nesse caso)
+    nullable: bool,
+    /// Nome lógico do campo, usado para compor o AAD do mesmo jeito que o resto do crate
+    /// (ver [`crypto::field_domain`] e `Encryptable::domain`)
+    field: &'static str,
+    /// Para colunas que guardam uma chave de dados embrulhada de um [`crate::blob_store::BlobStore`]
+    /// (ex. `audio_key_ciphertext`), o nome da coluna que guarda o handle do blob (ex.
+    /// `audio_path`). O AAD passa a ser [`crate::blob_store::blob_domain`] sobre esse handle, em
+    /// vez do domínio usual por entidade/id/campo - tem que bater com o AAD usado quando o
+    /// `BlobStore` embrulhou a chave originalmente. `None` para colunas "normais".
+    aad_handle_column: Option<&'static str>,
+}
+
+/// Uma tabela com uma ou mais colunas cifradas sob a chave mestra
+struct EncryptedTable {
+    /// Nome da tabela
+    table: &'static str,
+    /// Coluna usada como identificador da linha (texto, ordenável lexicograficamente)
+    id_column: &'static str,
+    /// Nome lógico da entidade, usado para compor o AAD (ex. `"patient"` para `patients`)
+    entity: &'static str,
+    /// Colunas cifradas desta tabela
+    columns: &'static [EncryptedColumn],
+}
+
+/// Tabelas com colunas cifradas sob a chave mestra, conhecidas por este binário
+const ENCRYPTED_TABLES: &[EncryptedTable] = &[
+    EncryptedTable {
+        table: "patients",
+        id_column: "id",
+        entity: "patient",
+        columns: &[
+            EncryptedColumn { ciphertext_column: "name_ciphertext", nonce_column: "name_nonce", nullable: false, field: "name", aad_handle_column: None },
+            EncryptedColumn { ciphertext_column: "phone_ciphertext", nonce_column: "phone_nonce", nullable: true, field: "phone", aad_handle_column: None },
+            EncryptedColumn { ciphertext_column: "email_ciphertext", nonce_column: "email_nonce", nullable: true, field: "email", aad_handle_column: None },
+        ],
+    },
+    EncryptedTable {
+        table: "anamneses",
+        id_column: "id",
+        entity: "anamnesis",
+        columns: &[
+            EncryptedColumn { ciphertext_column: "data_ciphertext", nonce_column: "data_nonce", nullable: false, field: "data", aad_handle_column: None },
+            EncryptedColumn { ciphertext_column: "diagnosis_ciphertext", nonce_column: "diagnosis_nonce", nullable: true, field: "diagnosis", aad_handle_column: None },
+            // Chave de dados do áudio, embrulhada pelo BlobStore sob a chave mestra - o AAD é o
+            // handle do blob (audio_path), não entidade/id/campo.
+            EncryptedColumn { ciphertext_column: "audio_key_ciphertext", nonce_column: "audio_key_nonce", nullable: true, field: "audio_key", aad_handle_column: Some("audio_path") },
+        ],
+    },
+    EncryptedTable {
+        table: "finances",
+        id_column: "id",
+        entity: "finance",
+        columns: &[
+            EncryptedColumn { ciphertext_column: "description_ciphertext", nonce_column: "description_nonce", nullable: true, field: "description", aad_handle_column: None },
+            // Chave de dados do comprovante, embrulhada pelo BlobStore sob a chave mestra - mesmo
+            // esquema de AAD por handle do blob (receipt_path) usado acima para audio_key.
+            EncryptedColumn { ciphertext_column: "receipt_key_ciphertext", nonce_column: "receipt_key_nonce", nullable: true, field: "receipt_key", aad_handle_column: Some("receipt_path") },
+        ],
+    },
+    EncryptedTable {
+        table: "appointments",
+        id_column: "id",
+        entity: "appointment",
+        columns: &[
+            EncryptedColumn { ciphertext_column: "notes_ciphertext", nonce_column: "notes_nonce", nullable: true, field: "notes", aad_handle_column: None },
+        ],
+    },
+];
+
+/// Cria a tabela de progresso `_key_rotation_progress`, caso ainda não exista
+async fn ensure_key_rotation_progress_table(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS _key_rotation_progress (
+            table_name TEXT PRIMARY KEY,
+            last_id TEXT,
+            completed BOOLEAN NOT NULL DEFAULT 0,
+            rotation_fingerprint BLOB,
+            updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("Falha ao criar tabela de progresso de rotação de chave")?;
+
+    Ok(())
+}
+
+/// Identifica, de forma não reversível, o par `(old_key, new_key)` de uma rotação
+///
+/// Guardado junto do checkpoint em `_key_rotation_progress` para distinguir o progresso de uma
+/// rotação do de outra: sem isso, uma rotação v1->v2 já concluída (`completed = 1`) faria uma
+/// chamada seguinte v2->v3 ser silenciosamente pulada, deixando os dados sob v2 enquanto
+/// `master_keys` já reporta v3 como ativa.
+fn rotation_fingerprint(old_key: &EncryptionKey, new_key: &EncryptionKey) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(old_key.as_bytes());
+    hasher.update(new_key.as_bytes());
+    hasher.finalize().to_vec()
+}
+
+/// Re-embrulha um único par ciphertext/nonce de `id` em `table`/`column`, de `old_key` para
+/// `new_key`. Não faz nada se a coluna for opcional e estiver NULL.
+async fn rotate_column(
+    tx: &mut Transaction<'_, Sqlite>,
+    table: &EncryptedTable,
+    column: &EncryptedColumn,
+    id: &str,
+    old_key: &EncryptionKey,
+    new_key: &EncryptionKey,
+) -> Result<()> {
+    let handle_select = match column.aad_handle_column {
+        Some(handle_column) => format!(", {}", handle_column),
+        None => String::new(),
+    };
+    let select = format!(
+        "SELECT {}, {}{} FROM {} WHERE {} = ?",
+        column.ciphertext_column, column.nonce_column, handle_select, table.table, table.id_column
+    );
+    let row = sqlx::query(&select)
+        .bind(id)
+        .fetch_one(&mut **tx)
+        .await
+        .context(format!(
+            "Falha ao ler {} de {} ({})",
+            column.ciphertext_column, table.table, id
+        ))?;
+    let ciphertext: Option<Vec<u8>> = row.try_get(column.ciphertext_column)?;
+    let nonce: Option<Vec<u8>> = row.try_get(column.nonce_column)?;
+
+    let (Some(ciphertext), Some(nonce)) = (ciphertext, nonce) else {
+        if !column.nullable {
+            anyhow::bail!(
+                "{} de {} ({}) é obrigatório mas está NULL",
+                column.ciphertext_column,
+                table.table,
+                id
+            );
+        }
+        return Ok(());
+    };
+
+    let aad = match column.aad_handle_column {
+        Some(handle_column) => {
+            let handle: Option<String> = row.try_get(handle_column)?;
+            let Some(handle) = handle else {
+                anyhow::bail!(
+                    "{} de {} ({}) está presente mas {} (handle do blob) está NULL",
+                    column.ciphertext_column,
+                    table.table,
+                    id,
+                    handle_column
+                );
+            };
+            crate::blob_store::blob_domain(&handle)
+        }
+        None => crypto::field_domain(table.entity, id, column.field),
+    };
+    let plaintext = crypto::decrypt_with_aad(&EncryptedData { ciphertext, nonce }, old_key, &aad)
+        .with_context(|| {
+            format!(
+                "Falha ao descriptografar {} de {} ({}) com a chave antiga",
+                column.ciphertext_column, table.table, id
+            )
+        })?;
+    let re_encrypted = crypto::encrypt_with_aad(plaintext.expose(), new_key, &aad)?;
+
+    let update = format!(
+        "UPDATE {} SET {} = ?, {} = ? WHERE {} = ?",
+        table.table, column.ciphertext_column, column.nonce_column, table.id_column
+    );
+    sqlx::query(&update)
+        .bind(re_encrypted.ciphertext)
+        .bind(re_encrypted.nonce)
+        .bind(id)
+        .execute(&mut **tx)
+        .await
+        .context(format!(
+            "Falha ao gravar {} rotacionado em {} ({})",
+            column.ciphertext_column, table.table, id
+        ))?;
+
+    Ok(())
+}
+
+/// Rotaciona todas as colunas cifradas de `table`, em lotes de [`ROTATION_BATCH_SIZE`] linhas,
+/// cada lote confirmado em sua própria transação junto com o avanço do cursor em
+/// `_key_rotation_progress` - uma falha no meio do caminho deixa o cursor na última linha
+/// efetivamente rotacionada, e uma nova chamada continua dali (idempotente: linhas já
+/// rotacionadas ficam para trás do cursor e não são tocadas de novo).
+async fn rotate_table(
+    pool: &SqlitePool,
+    table: &EncryptedTable,
+    old_key: &EncryptionKey,
+    new_key: &EncryptionKey,
+) -> Result<()> {
+    let fingerprint = rotation_fingerprint(old_key, new_key);
+
+    let progress: Option<(Option<String>, bool, Option<Vec<u8>>)> = sqlx::query_as(
+        "SELECT last_id, completed, rotation_fingerprint FROM _key_rotation_progress WHERE table_name = ?",
+    )
+    .bind(table.table)
+    .fetch_optional(pool)
+    .await
+    .context(format!("Falha ao ler progresso de rotação de {}", table.table))?;
+
+    // Um checkpoint só é válido para o par de chaves que o produziu - se pertence a outra
+    // rotação (ex.: v1->v2 já concluída, e agora estamos em v2->v3), ignora-o e começa do zero
+    // em vez de reaproveitar um `completed`/`last_id` que não corresponde a esta chamada.
+    let progress = match progress {
+        Some((last_id, completed, stored_fingerprint))
+            if stored_fingerprint.as_deref() == Some(fingerprint.as_slice()) =>
+        {
+            Some((last_id, completed))
+        }
+        Some(_) => {
+            info!(
+                "Checkpoint de rotação de {} pertence a outro par de chaves, reiniciando",
+                table.table
+            );
+            None
+        }
+        None => None,
+    };
+
+    if let Some((_, true)) = progress {
+        info!("Tabela {} já rotacionada, pulando", table.table);
+        return Ok(());
+    }
+
+    let mut last_id = progress.and_then(|(id, _)| id);
+
+    loop {
+        let mut tx = pool.begin().await
+            .context(format!("Falha ao iniciar transação de rotação em {}", table.table))?;
+
+        let select_ids = format!(
+            "SELECT {id_col} FROM {tbl} WHERE (? IS NULL OR {id_col} > ?) ORDER BY {id_col} ASC LIMIT ?",
+            id_col = table.id_column,
+            tbl = table.table,
+        );
+        let ids: Vec<String> = sqlx::query_scalar(&select_ids)
+            .bind(&last_id)
+            .bind(&last_id)
+            .bind(ROTATION_BATCH_SIZE)
+            .fetch_all(&mut *tx)
+            .await
+            .context(format!("Falha ao listar linhas pendentes de {}", table.table))?;
+
+        if ids.is_empty() {
+            sqlx::query(
+                "INSERT INTO _key_rotation_progress (table_name, last_id, completed, rotation_fingerprint, updated_at)
+                 VALUES (?, ?, 1, ?, CURRENT_TIMESTAMP)
+                 ON CONFLICT(table_name) DO UPDATE SET
+                     completed = 1, rotation_fingerprint = excluded.rotation_fingerprint, updated_at = CURRENT_TIMESTAMP",
+            )
+            .bind(table.table)
+            .bind(&last_id)
+            .bind(&fingerprint)
+            .execute(&mut *tx)
+            .await
+            .context(format!("Falha ao marcar {} como rotacionada", table.table))?;
+
+            tx.commit().await.context(format!("Falha ao confirmar conclusão de {}", table.table))?;
+            info!("Tabela {} totalmente rotacionada", table.table);
+            break;
+        }
+
+        for id in &ids {
+            for column in table.columns {
+                rotate_column(&mut tx, table, column, id, old_key, new_key).await?;
+            }
+        }
+
+        let new_last_id = ids.last().cloned();
+        sqlx::query(
+            "INSERT INTO _key_rotation_progress (table_name, last_id, completed, rotation_fingerprint, updated_at)
+             VALUES (?, ?, 0, ?, CURRENT_TIMESTAMP)
+             ON CONFLICT(table_name) DO UPDATE SET
+                 last_id = excluded.last_id, completed = 0, rotation_fingerprint = excluded.rotation_fingerprint, updated_at = CURRENT_TIMESTAMP",
+        )
+        .bind(table.table)
+        .bind(&new_last_id)
+        .bind(&fingerprint)
+        .execute(&mut *tx)
+        .await
+        .context(format!("Falha ao salvar progresso de rotação em {}", table.table))?;
+
+        tx.commit().await.context(format!("Falha ao confirmar lote de rotação em {}", table.table))?;
+
+        info!("Rotacionadas {} linha(s) de {} (cursor: {:?})", ids.len(), table.table, new_last_id);
+        last_id = new_last_id;
+    }
+
+    Ok(())
+}
+
+/// Rotaciona os dados já cifrados de `old_key` para `new_key`, em todas as [`ENCRYPTED_TABLES`]
+/// conhecidas.
+///
+/// É o complemento, no plano de dados, da rotação de chave mestra já feita por
+/// [`crate::master_key::rotate_master_key`] (que cuida da senha/linha em `master_keys`): depois
+/// que a nova versão da chave mestra existe, este migra o conteúdo cifrado das tabelas para ela.
+///
+/// Diferente de uma migração de schema, cada tabela é processada em lotes e confirmada
+/// separadamente (não há uma única transação para tudo) - uma falha no meio do caminho não
+/// desfaz o que já foi rotacionado, e uma nova chamada com os mesmos `old_key`/`new_key` retoma
+/// de onde parou sem re-processar linhas já migradas.
+pub async fn rotate_master_key(
+    pool: &SqlitePool,
+    old_key: &EncryptionKey,
+    new_key: &EncryptionKey,
+) -> Result<()> {
+    ensure_key_rotation_progress_table(pool).await?;
+
+    for table in ENCRYPTED_TABLES {
+        rotate_table(pool, table, old_key, new_key).await?;
+    }
+
+    info!("Rotação de chave de dados concluída em todas as tabelas cifradas");
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::blob_store;
     use sqlx::sqlite::SqliteConnectOptions;
-    use std::path::Path;
     use tempfile::tempdir;
-    
-    #[tokio::test]
-    async fn test_migrations() -> Result<()> {
-        // Usar diretório temporário para testes
+
+    async fn fresh_pool(name: &str) -> Result<SqlitePool> {
         let temp_dir = tempdir()?;
-        let db_path = temp_dir.path().join("test_migrations.db");
+        let db_path = temp_dir.path().join(name);
         let db_url = format!("sqlite:{}", db_path.display());
-        
-        // Criar banco de dados
         Sqlite::create_database(&db_url).await?;
-        
-        // Conectar
         let conn_options = SqliteConnectOptions::new()
             .filename(&db_path)
             .create_if_missing(true);
-            
-        let pool = SqlitePool::connect_with(conn_options).await?;
-        
-        // Aplicar migrações
+        Ok(SqlitePool::connect_with(conn_options).await?)
+    }
+
+    #[test]
+    fn test_parse_migration_filename() {
+        assert!(matches!(
+            parse_migration_filename("001_initial_schema.up.sql"),
+            Some((1, name, Direction::Up)) if name == "initial_schema"
+        ));
+        assert!(matches!(
+            parse_migration_filename("003_master_key_rotation.down.sql"),
+            Some((3, name, Direction::Down)) if name == "master_key_rotation"
+        ));
+        assert!(parse_migration_filename("README.md").is_none());
+        assert!(parse_migration_filename("semver_not_a_version.up.sql").is_none());
+    }
+
+    #[test]
+    fn test_migrations_loaded_and_sorted() {
+        let loaded = migrations();
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded[0].version, 1);
+        assert_eq!(loaded[0].name, "initial_schema");
+        assert_eq!(loaded[1].version, 2);
+        assert_eq!(loaded[2].version, 3);
+    }
+
+    #[tokio::test]
+    async fn test_migrations() -> Result<()> {
+        let pool = fresh_pool("test_migrations.db").await?;
+
         run_migrations(&pool).await?;
-        
-        // Verificar versão do banco
-        let version: i64 = sqlx::query_scalar("PRAGMA user_version")
-            .fetch_one(&pool)
-            .await?;
-            
-        assert_eq!(version, MIGRATIONS.len() as i64);
-        
-        // Verificar se tabelas foram criadas
+
+        let version = current_version(&pool).await?;
+        assert_eq!(version, migrations().len() as i64);
+
+        // Verifica se tabelas foram criadas
         let tables: Vec<String> = sqlx::query_scalar(
             "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%'"
         )
         .fetch_all(&pool)
         .await?;
-        
-        // Verificar algumas tabelas esperadas
+
         assert!(tables.contains(&"patients".to_string()));
         assert!(tables.contains(&"appointments".to_string()));
         assert!(tables.contains(&"anamneses".to_string()));
         assert!(tables.contains(&"finances".to_string()));
         assert!(tables.contains(&"master_keys".to_string()));
         assert!(tables.contains(&"system_integrations".to_string()));
-        
+        assert!(tables.contains(&"_migrations".to_string()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_migrations_record_checksums() -> Result<()> {
+        let pool = fresh_pool("test_migrations_checksums.db").await?;
+
+        run_migrations(&pool).await?;
+
+        let rows: Vec<(i64, Vec<u8>)> = sqlx::query_as("SELECT version, checksum FROM _migrations ORDER BY version")
+            .fetch_all(&pool)
+            .await?;
+
+        assert_eq!(rows.len(), migrations().len());
+        for (i, (version, stored_checksum)) in rows.iter().enumerate() {
+            assert_eq!(*version, migrations()[i].version);
+            assert_eq!(*stored_checksum, checksum(migrations()[i].up));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_migrations_rejects_tampered_history() -> Result<()> {
+        let pool = fresh_pool("test_tampered_history.db").await?;
+
+        run_migrations(&pool).await?;
+
+        // Simula uma edição no script de uma migração já aplicada
+        sqlx::query("UPDATE _migrations SET checksum = ? WHERE version = 1")
+            .bind(vec![0u8; 32])
+            .execute(&pool)
+            .await?;
+
+        let result = run_migrations_to(&pool, migrations().len() as i64).await;
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_revert_migrations() -> Result<()> {
+        let pool = fresh_pool("test_revert_migrations.db").await?;
+
+        run_migrations(&pool).await?;
+
+        // Reverter até a versão 1: as tabelas da migração 002 devem sumir
+        revert_migrations(&pool, 1).await?;
+
+        let version = current_version(&pool).await?;
+        assert_eq!(version, 1);
+
+        let tables: Vec<String> = sqlx::query_scalar(
+            "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%'"
+        )
+        .fetch_all(&pool)
+        .await?;
+
+        assert!(tables.contains(&"patients".to_string()));
+        assert!(!tables.contains(&"system_integrations".to_string()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_migrations_to_target_version() -> Result<()> {
+        let pool = fresh_pool("test_target_version.db").await?;
+
+        // Subir só até a versão 1
+        run_migrations_to(&pool, 1).await?;
+        assert_eq!(current_version(&pool).await?, 1);
+
+        // Rodar de novo até a mesma versão é um no-op
+        run_migrations_to(&pool, 1).await?;
+        assert_eq!(current_version(&pool).await?, 1);
+
+        // Subir o resto
+        run_migrations_to(&pool, migrations().len() as i64).await?;
+        assert_eq!(current_version(&pool).await?, migrations().len() as i64);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_migrations_to_rejects_out_of_range_versions() -> Result<()> {
+        let pool = fresh_pool("test_out_of_range.db").await?;
+
+        // Versão acima da última migração conhecida
+        assert!(run_migrations_to(&pool, migrations().len() as i64 + 1).await.is_err());
+
+        // Subir tudo e então tentar "subir" para uma versão anterior
+        run_migrations(&pool).await?;
+        assert!(run_migrations_to(&pool, 1).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_migration_status_reports_pending_and_applied() -> Result<()> {
+        let pool = fresh_pool("test_migration_status.db").await?;
+
+        run_migrations_to(&pool, 1).await?;
+
+        let statuses = migration_status(&pool).await?;
+        assert_eq!(statuses.len(), migrations().len());
+
+        assert_eq!(statuses[0].version, 1);
+        assert_eq!(statuses[0].state, MigrationState::Applied);
+        assert!(statuses[0].applied_at.is_some());
+        assert!(statuses[0].checksum.is_some());
+
+        assert_eq!(statuses[1].state, MigrationState::Pending);
+        assert!(statuses[1].applied_at.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_migration_status_reports_unknown_db_version() -> Result<()> {
+        let pool = fresh_pool("test_migration_status_unknown.db").await?;
+
+        run_migrations(&pool).await?;
+
+        // Simula uma migração aplicada por um binário mais novo que este
+        sqlx::query(
+            "INSERT INTO _migrations (version, name, checksum, applied_at, execution_ms) VALUES (?, ?, ?, CURRENT_TIMESTAMP, ?)",
+        )
+        .bind(migrations().len() as i64 + 1)
+        .bind("999_futura")
+        .bind(vec![0u8; 32])
+        .bind(0i64)
+        .execute(&pool)
+        .await?;
+
+        let statuses = migration_status(&pool).await?;
+        let unknown = statuses.last().unwrap();
+        assert_eq!(unknown.version, migrations().len() as i64 + 1);
+        assert_eq!(unknown.state, MigrationState::AppliedButUnknown);
+        assert_eq!(unknown.name, "999_futura");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_revert_migrations_rejects_out_of_range_versions() -> Result<()> {
+        let pool = fresh_pool("test_revert_out_of_range.db").await?;
+        run_migrations(&pool).await?;
+
+        // Versão acima da atual não é reversão válida
+        assert!(revert_migrations(&pool, migrations().len() as i64 + 1).await.is_err());
+
+        // Versão negativa é inválida
+        assert!(revert_migrations(&pool, -1).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_revert_migrations_returns_error_for_db_ahead_of_binary() -> Result<()> {
+        let pool = fresh_pool("test_revert_ahead_of_binary.db").await?;
+        run_migrations(&pool).await?;
+
+        // Simula uma migração aplicada por um binário mais novo que este (banco à frente)
+        sqlx::query(
+            "INSERT INTO _migrations (version, name, checksum, applied_at, execution_ms) VALUES (?, ?, ?, CURRENT_TIMESTAMP, ?)",
+        )
+        .bind(migrations().len() as i64 + 1)
+        .bind("999_futura")
+        .bind(vec![0u8; 32])
+        .bind(0i64)
+        .execute(&pool)
+        .await?;
+
+        // Reverter deve retornar um erro, não panicar o processo
+        let result = revert_migrations(&pool, 1).await;
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rotate_master_key_reencrypts_data() -> Result<()> {
+        let pool = fresh_pool("test_rotate_master_key.db").await?;
+        run_migrations(&pool).await?;
+
+        let old_key = EncryptionKey::generate();
+        let new_key = EncryptionKey::generate();
+
+        let patient_id = "11111111-1111-1111-1111-111111111111";
+        let aad = crypto::field_domain("patient", patient_id, "name");
+        let encrypted = crypto::encrypt_with_aad(b"Maria da Silva", &old_key, &aad)?;
+
+        sqlx::query("INSERT INTO patients (id, name_ciphertext, name_nonce) VALUES (?, ?, ?)")
+            .bind(patient_id)
+            .bind(&encrypted.ciphertext)
+            .bind(&encrypted.nonce)
+            .execute(&pool)
+            .await?;
+
+        rotate_master_key(&pool, &old_key, &new_key).await?;
+
+        let (ciphertext, nonce): (Vec<u8>, Vec<u8>) =
+            sqlx::query_as("SELECT name_ciphertext, name_nonce FROM patients WHERE id = ?")
+                .bind(patient_id)
+                .fetch_one(&pool)
+                .await?;
+
+        let rotated = EncryptedData { ciphertext, nonce };
+        let decrypted = crypto::decrypt_with_aad(&rotated, &new_key, &aad)?;
+        assert_eq!(decrypted.expose().as_slice(), b"Maria da Silva");
+
+        // A chave antiga não deve mais conseguir abrir o ciphertext rotacionado
+        assert!(crypto::decrypt_with_aad(&rotated, &old_key, &aad).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rotate_master_key_is_idempotent() -> Result<()> {
+        let pool = fresh_pool("test_rotate_master_key_idempotent.db").await?;
+        run_migrations(&pool).await?;
+
+        let old_key = EncryptionKey::generate();
+        let new_key = EncryptionKey::generate();
+
+        let patient_id = "22222222-2222-2222-2222-222222222222";
+        let aad = crypto::field_domain("patient", patient_id, "name");
+        let encrypted = crypto::encrypt_with_aad(b"Joao Souza", &old_key, &aad)?;
+
+        sqlx::query("INSERT INTO patients (id, name_ciphertext, name_nonce) VALUES (?, ?, ?)")
+            .bind(patient_id)
+            .bind(&encrypted.ciphertext)
+            .bind(&encrypted.nonce)
+            .execute(&pool)
+            .await?;
+
+        rotate_master_key(&pool, &old_key, &new_key).await?;
+        // Chamar de novo não deve re-rotacionar (e não deve falhar tentando abrir com old_key
+        // um ciphertext que já está sob new_key)
+        rotate_master_key(&pool, &old_key, &new_key).await?;
+
+        let (ciphertext, nonce): (Vec<u8>, Vec<u8>) =
+            sqlx::query_as("SELECT name_ciphertext, name_nonce FROM patients WHERE id = ?")
+                .bind(patient_id)
+                .fetch_one(&pool)
+                .await?;
+
+        let decrypted =
+            crypto::decrypt_with_aad(&EncryptedData { ciphertext, nonce }, &new_key, &aad)?;
+        assert_eq!(decrypted.expose().as_slice(), b"Joao Souza");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rotate_master_key_resumes_from_checkpoint() -> Result<()> {
+        let pool = fresh_pool("test_rotate_master_key_resume.db").await?;
+        run_migrations(&pool).await?;
+
+        let old_key = EncryptionKey::generate();
+        let new_key = EncryptionKey::generate();
+
+        let patient_id = "33333333-3333-3333-3333-333333333333";
+        let aad = crypto::field_domain("patient", patient_id, "name");
+        let encrypted = crypto::encrypt_with_aad(b"Ana Pereira", &old_key, &aad)?;
+
+        sqlx::query("INSERT INTO patients (id, name_ciphertext, name_nonce) VALUES (?, ?, ?)")
+            .bind(patient_id)
+            .bind(&encrypted.ciphertext)
+            .bind(&encrypted.nonce)
+            .execute(&pool)
+            .await?;
+
+        // Simula uma rotação anterior que só chegou a marcar o cursor na linha já rotacionada,
+        // sem concluir a tabela
+        ensure_key_rotation_progress_table(&pool).await?;
+        let already_rotated = crypto::encrypt_with_aad(b"Ana Pereira", &new_key, &aad)?;
+        sqlx::query("UPDATE patients SET name_ciphertext = ?, name_nonce = ? WHERE id = ?")
+            .bind(&already_rotated.ciphertext)
+            .bind(&already_rotated.nonce)
+            .bind(patient_id)
+            .execute(&pool)
+            .await?;
+        sqlx::query(
+            "INSERT INTO _key_rotation_progress (table_name, last_id, completed, rotation_fingerprint, updated_at)
+             VALUES (?, ?, 0, ?, CURRENT_TIMESTAMP)",
+        )
+        .bind("patients")
+        .bind(patient_id)
+        .bind(rotation_fingerprint(&old_key, &new_key))
+        .execute(&pool)
+        .await?;
+
+        // Retomar não deve tentar reabrir a linha já rotacionada com a chave antiga
+        rotate_master_key(&pool, &old_key, &new_key).await?;
+
+        let (ciphertext, nonce): (Vec<u8>, Vec<u8>) =
+            sqlx::query_as("SELECT name_ciphertext, name_nonce FROM patients WHERE id = ?")
+                .bind(patient_id)
+                .fetch_one(&pool)
+                .await?;
+
+        let decrypted =
+            crypto::decrypt_with_aad(&EncryptedData { ciphertext, nonce }, &new_key, &aad)?;
+        assert_eq!(decrypted.expose().as_slice(), b"Ana Pereira");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rotate_master_key_reencrypts_wrapped_blob_data_keys() -> Result<()> {
+        let pool = fresh_pool("test_rotate_master_key_blob_keys.db").await?;
+        run_migrations(&pool).await?;
+
+        let old_key = EncryptionKey::generate();
+        let new_key = EncryptionKey::generate();
+
+        // audio_key_ciphertext/audio_key_nonce e receipt_key_ciphertext/receipt_key_nonce guardam
+        // uma chave de dados de BlobStore embrulhada sob a chave mestra, com AAD = blob_domain do
+        // handle (ver crate::blob_store::blob_domain), não field_domain(entidade, id, campo).
+        let patient_id = "44444444-4444-4444-4444-444444444444";
+        let patient_name = crypto::encrypt_with_aad(
+            b"Paciente",
+            &old_key,
+            &crypto::field_domain("patient", patient_id, "name"),
+        )?;
+        sqlx::query(
+            "INSERT INTO patients (id, name_ciphertext, name_nonce) VALUES (?, ?, ?)",
+        )
+        .bind(patient_id)
+        .bind(&patient_name.ciphertext)
+        .bind(&patient_name.nonce)
+        .execute(&pool)
+        .await?;
+
+        let appointment_id = "55555555-5555-5555-5555-555555555555";
+        sqlx::query(
+            "INSERT INTO appointments (id, patient_id, scheduled_at, status, type, source)
+             VALUES (?, ?, CURRENT_TIMESTAMP, 'scheduled', 'consulta', 'app')",
+        )
+        .bind(appointment_id)
+        .bind(patient_id)
+        .execute(&pool)
+        .await?;
+
+        let audio_path = "audio-handle-1";
+        let audio_key_aad = blob_store::blob_domain(audio_path);
+        let wrapped_audio_key = crypto::encrypt_with_aad(b"chave-de-dados-do-audio", &old_key, &audio_key_aad)?;
+
+        let anamnesis_id = "66666666-6666-6666-6666-666666666666";
+        let anamnesis_data = crypto::encrypt_with_aad(
+            b"Dados da anamnese",
+            &old_key,
+            &crypto::field_domain("anamnesis", anamnesis_id, "data"),
+        )?;
+        sqlx::query(
+            "INSERT INTO anamneses (id, appointment_id, data_ciphertext, data_nonce, audio_path, audio_key_ciphertext, audio_key_nonce)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(anamnesis_id)
+        .bind(appointment_id)
+        .bind(&anamnesis_data.ciphertext)
+        .bind(&anamnesis_data.nonce)
+        .bind(audio_path)
+        .bind(&wrapped_audio_key.ciphertext)
+        .bind(&wrapped_audio_key.nonce)
+        .execute(&pool)
+        .await?;
+
+        let receipt_path = "receipt-handle-1";
+        let receipt_key_aad = blob_store::blob_domain(receipt_path);
+        let wrapped_receipt_key = crypto::encrypt_with_aad(b"chave-de-dados-do-comprovante", &old_key, &receipt_key_aad)?;
+
+        let finance_id = "77777777-7777-7777-7777-777777777777";
+        sqlx::query(
+            "INSERT INTO finances (id, type, category, amount, date, receipt_path, receipt_key_ciphertext, receipt_key_nonce)
+             VALUES (?, 'expense', 'material', 10.0, '2024-01-01', ?, ?, ?)",
+        )
+        .bind(finance_id)
+        .bind(receipt_path)
+        .bind(&wrapped_receipt_key.ciphertext)
+        .bind(&wrapped_receipt_key.nonce)
+        .execute(&pool)
+        .await?;
+
+        rotate_master_key(&pool, &old_key, &new_key).await?;
+
+        let (audio_ciphertext, audio_nonce): (Vec<u8>, Vec<u8>) = sqlx::query_as(
+            "SELECT audio_key_ciphertext, audio_key_nonce FROM anamneses WHERE id = ?",
+        )
+        .bind(anamnesis_id)
+        .fetch_one(&pool)
+        .await?;
+        let rotated_audio_key = EncryptedData { ciphertext: audio_ciphertext, nonce: audio_nonce };
+        let decrypted_audio_key = crypto::decrypt_with_aad(&rotated_audio_key, &new_key, &audio_key_aad)?;
+        assert_eq!(decrypted_audio_key.expose().as_slice(), b"chave-de-dados-do-audio");
+        assert!(crypto::decrypt_with_aad(&rotated_audio_key, &old_key, &audio_key_aad).is_err());
+
+        let (receipt_ciphertext, receipt_nonce): (Vec<u8>, Vec<u8>) = sqlx::query_as(
+            "SELECT receipt_key_ciphertext, receipt_key_nonce FROM finances WHERE id = ?",
+        )
+        .bind(finance_id)
+        .fetch_one(&pool)
+        .await?;
+        let rotated_receipt_key = EncryptedData { ciphertext: receipt_ciphertext, nonce: receipt_nonce };
+        let decrypted_receipt_key = crypto::decrypt_with_aad(&rotated_receipt_key, &new_key, &receipt_key_aad)?;
+        assert_eq!(decrypted_receipt_key.expose().as_slice(), b"chave-de-dados-do-comprovante");
+        assert!(crypto::decrypt_with_aad(&rotated_receipt_key, &old_key, &receipt_key_aad).is_err());
+
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_rotate_master_key_does_not_skip_a_later_rotation_with_a_different_key_pair() -> Result<()> {
+        let pool = fresh_pool("test_rotate_master_key_successive_pairs.db").await?;
+        run_migrations(&pool).await?;
+
+        let key_v1 = EncryptionKey::generate();
+        let key_v2 = EncryptionKey::generate();
+        let key_v3 = EncryptionKey::generate();
+
+        let patient_id = "88888888-8888-8888-8888-888888888888";
+        let aad = crypto::field_domain("patient", patient_id, "name");
+        let encrypted = crypto::encrypt_with_aad(b"Carlos Mendes", &key_v1, &aad)?;
+
+        sqlx::query("INSERT INTO patients (id, name_ciphertext, name_nonce) VALUES (?, ?, ?)")
+            .bind(patient_id)
+            .bind(&encrypted.ciphertext)
+            .bind(&encrypted.nonce)
+            .execute(&pool)
+            .await?;
+
+        // Rotação v1 -> v2: conclui e marca todas as tabelas como `completed`
+        rotate_master_key(&pool, &key_v1, &key_v2).await?;
+
+        // Uma rotação seguinte v2 -> v3 (outro par de chaves) não pode ser pulada só porque
+        // `_key_rotation_progress` já diz `completed = 1` da rotação anterior
+        rotate_master_key(&pool, &key_v2, &key_v3).await?;
+
+        let (ciphertext, nonce): (Vec<u8>, Vec<u8>) =
+            sqlx::query_as("SELECT name_ciphertext, name_nonce FROM patients WHERE id = ?")
+                .bind(patient_id)
+                .fetch_one(&pool)
+                .await?;
+
+        let rotated = EncryptedData { ciphertext, nonce };
+        let decrypted = crypto::decrypt_with_aad(&rotated, &key_v3, &aad)?;
+        assert_eq!(decrypted.expose().as_slice(), b"Carlos Mendes");
+
+        // Nem a v1 nem a v2 devem mais conseguir abrir o ciphertext
+        assert!(crypto::decrypt_with_aad(&rotated, &key_v1, &aad).is_err());
+        assert!(crypto::decrypt_with_aad(&rotated, &key_v2, &aad).is_err());
+
+        Ok(())
+    }
+}
@@ -2,12 +2,15 @@
 //!
 //! Este módulo define as estruturas de dados principais usadas pelo ecossistema da clínica
 
+use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::sqlite::SqliteRow;
 use sqlx::{FromRow, Row};
 use uuid::Uuid;
 
+use crate::crypto::{self, CryptoError, Encryptable, EncryptedData, EncryptionKey};
+
 /// Status possíveis de um agendamento
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -160,6 +163,79 @@ pub struct EncryptedAnamnesis {
     pub diagnosis_ciphertext: Option<Vec<u8>>,
     /// Nonce para o diagnóstico
     pub diagnosis_nonce: Option<Vec<u8>>,
+    /// Visão descriptografada dos campos sensíveis - nunca persistida, só existe em memória
+    /// entre um `decrypt_fields` e o `encrypt_fields` seguinte
+    #[serde(skip)]
+    pub plaintext: AnamnesisPlaintext,
+}
+
+/// Plaintext dos campos sensíveis de uma [`EncryptedAnamnesis`]
+///
+/// Mantido separado da forma persistida para que nenhum código grave plaintext no banco
+/// por engano: só é possível obter os dados reais chamando `decrypt_fields`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnamnesisPlaintext {
+    /// Dados do formulário de anamnese (texto/transcrição)
+    pub data: Option<String>,
+    /// Hipóteses diagnósticas
+    pub diagnosis: Option<String>,
+}
+
+impl Encryptable for EncryptedAnamnesis {
+    fn encrypt_fields(&mut self, key: &EncryptionKey) -> Result<()> {
+        if let Some(data) = self.plaintext.data.take() {
+            let aad = self.domain("data");
+            let encrypted = crypto::encrypt_with_aad(data.as_bytes(), key, &aad)?;
+            self.data_ciphertext = encrypted.ciphertext;
+            self.data_nonce = encrypted.nonce;
+        }
+
+        if let Some(diagnosis) = self.plaintext.diagnosis.take() {
+            let aad = self.domain("diagnosis");
+            let encrypted = crypto::encrypt_with_aad(diagnosis.as_bytes(), key, &aad)?;
+            self.diagnosis_ciphertext = Some(encrypted.ciphertext);
+            self.diagnosis_nonce = Some(encrypted.nonce);
+        }
+
+        Ok(())
+    }
+
+    fn decrypt_fields(&mut self, key: &EncryptionKey) -> Result<()> {
+        let data_aad = self.domain("data");
+        let data = crypto::decrypt_with_aad(
+            &EncryptedData {
+                ciphertext: self.data_ciphertext.clone(),
+                nonce: self.data_nonce.clone(),
+            },
+            key,
+            &data_aad,
+        )?;
+        self.plaintext.data = Some(
+            String::from_utf8(data.expose().clone())
+                .map_err(|e| CryptoError::InvalidData(e.to_string()))?,
+        );
+
+        if let (Some(ciphertext), Some(nonce)) =
+            (self.diagnosis_ciphertext.clone(), self.diagnosis_nonce.clone())
+        {
+            let diagnosis_aad = self.domain("diagnosis");
+            let diagnosis = crypto::decrypt_with_aad(
+                &EncryptedData { ciphertext, nonce },
+                key,
+                &diagnosis_aad,
+            )?;
+            self.plaintext.diagnosis = Some(
+                String::from_utf8(diagnosis.expose().clone())
+                    .map_err(|e| CryptoError::InvalidData(e.to_string()))?,
+            );
+        }
+
+        Ok(())
+    }
+
+    fn domain(&self, field_name: &str) -> Vec<u8> {
+        crypto::field_domain("anamnesis", self.id, field_name)
+    }
 }
 
 /// Modelo para registrar métricas de redes sociais
@@ -186,7 +262,7 @@ pub struct SocialMetrics {
 }
 
 /// Registro da chave mestra criptografada
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct MasterKey {
     /// Identificador único
     pub id: i32,
@@ -198,8 +274,83 @@ pub struct MasterKey {
     pub wrapped_key_ciphertext: Vec<u8>,
     /// Nonce para a chave mestra
     pub wrapped_key_nonce: Vec<u8>,
-    /// Tag para autenticação
+    /// Tag para autenticação (não usada - a tag AEAD já vai embutida em `wrapped_key_ciphertext`;
+    /// mantida por compatibilidade com o schema original)
     pub wrapped_key_tag: Vec<u8>,
     /// Versão da chave
     pub key_version: i32,
+    /// Salt Argon2id usado para derivar a chave de embrulho a partir da senha do admin
+    pub wrapped_key_salt: Vec<u8>,
+    /// Custo de memória Argon2id (KiB) usado na derivação
+    pub wrapped_key_m_cost: i32,
+    /// Número de iterações Argon2id usado na derivação
+    pub wrapped_key_t_cost: i32,
+    /// Grau de paralelismo Argon2id usado na derivação
+    pub wrapped_key_p_cost: i32,
+    /// Sentinela fixo cifrado com a mesma chave de embrulho, usado para validar a senha do
+    /// admin sem precisar desembrulhar a chave mestra real
+    pub verification_ciphertext: Vec<u8>,
+    /// Nonce do blob de verificação
+    pub verification_nonce: Vec<u8>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_anamnesis(id: Uuid) -> EncryptedAnamnesis {
+        EncryptedAnamnesis {
+            id,
+            appointment_id: Uuid::new_v4(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            data_ciphertext: Vec::new(),
+            data_nonce: Vec::new(),
+            audio_path: None,
+            audio_key_ciphertext: None,
+            audio_key_nonce: None,
+            transcription_complete: false,
+            diagnosis_ciphertext: None,
+            diagnosis_nonce: None,
+            plaintext: AnamnesisPlaintext::default(),
+        }
+    }
+
+    #[test]
+    fn test_encrypted_anamnesis_encrypt_decrypt_roundtrip() -> Result<()> {
+        let key = EncryptionKey::generate();
+        let mut anamnesis = sample_anamnesis(Uuid::new_v4());
+        anamnesis.plaintext.data = Some("Paciente relata dor lombar ha duas semanas".to_string());
+        anamnesis.plaintext.diagnosis = Some("Suspeita de lombalgia mecanica".to_string());
+
+        anamnesis.encrypt_fields(&key)?;
+        assert!(!anamnesis.data_ciphertext.is_empty());
+        assert!(anamnesis.diagnosis_ciphertext.is_some());
+        // `encrypt_fields` consome o plaintext - nada deve sobrar em memória além do ciphertext
+        assert!(anamnesis.plaintext.data.is_none());
+        assert!(anamnesis.plaintext.diagnosis.is_none());
+
+        anamnesis.decrypt_fields(&key)?;
+        assert_eq!(anamnesis.plaintext.data.as_deref(), Some("Paciente relata dor lombar ha duas semanas"));
+        assert_eq!(anamnesis.plaintext.diagnosis.as_deref(), Some("Suspeita de lombalgia mecanica"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encrypted_anamnesis_aad_binds_ciphertext_to_its_id() -> Result<()> {
+        let key = EncryptionKey::generate();
+        let mut anamnesis = sample_anamnesis(Uuid::new_v4());
+        anamnesis.plaintext.data = Some("Dado sensivel de anamnese".to_string());
+        anamnesis.encrypt_fields(&key)?;
+
+        // Ciphertext "transplantado" para uma anamnese com outro id (AAD diferente): deve falhar
+        let mut other = sample_anamnesis(Uuid::new_v4());
+        other.data_ciphertext = anamnesis.data_ciphertext.clone();
+        other.data_nonce = anamnesis.data_nonce.clone();
+
+        assert!(other.decrypt_fields(&key).is_err());
+
+        Ok(())
+    }
 }
\ No newline at end of file